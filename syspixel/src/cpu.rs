@@ -53,14 +53,14 @@ impl Cpu {
             .cpu_times_percent_percpu()
             .unwrap()
             .iter()
-            .map(|cpu| ((100.0 - cpu.idle()) / 100.0) as f64)
+            .map(|cpu| (100.0 - cpu.idle()) as f64 / 100.0)
             .collect();
 
         let mut rng = rand::thread_rng();
         for (cpu_id, quarter) in self.quarters.iter().enumerate() {
             for px in quarter {
                 let load = loads[cpu_id];
-                pixels[*px] = rng.gen_bool(load as f64);
+                pixels[*px] = rng.gen_bool(load);
             }
         }
 
@@ -15,7 +15,11 @@ static GREEN: RGB8 = RGB8::new(0, 20, 0);
 static RED: RGB8 = RGB8::new(70, 00, 0);
 static BLACK: RGB8 = RGB8::new(0, 0, 0);
 
-fn go<T: Display>(mut display: T) -> Result<()> {
+fn go<T: Display>(mut display: T, brightness: Option<f32>) -> Result<()> {
+    if let Some(brightness) = brightness {
+        display.set_brightness(brightness);
+    }
+
     let mut pixels = {
         let num_dots = display.dimensions().num_px();
         PixelGrid::new(num_dots)
@@ -36,7 +40,9 @@ fn go<T: Display>(mut display: T) -> Result<()> {
             display.set_idx(idx, &rgb);
         }
 
-        display.flush();
+        if let Err(e) = display.flush() {
+            log::error!("Failed to update display: {}", e);
+        }
 
         std::thread::sleep(Duration::from_millis(1000));
     }
@@ -48,6 +54,12 @@ fn go<T: Display>(mut display: T) -> Result<()> {
 struct Cli {
     #[clap(subcommand)]
     command: Option<Mode>,
+
+    /// Set the display's brightness, from 0.0 (off) to 1.0 (full). This is
+    /// absolute, not relative to the board's default: the HD starts at
+    /// full, but the Mini at 1/63, so most values make it brighter
+    #[clap(long)]
+    brightness: Option<f32>,
 }
 
 // #[derive(Clone)]
@@ -60,12 +72,14 @@ enum Mode {
 fn main() -> Result<()> {
     env_logger::init();
 
-    match Cli::parse().command {
-        Some(Mode::UnicornMini ) => go(UnicornMini::new())?,
-        Some(Mode::Unicorn ) => go(Unicorn::new())?,
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Mode::UnicornMini ) => go(UnicornMini::try_new()?, cli.brightness)?,
+        Some(Mode::Unicorn ) => go(Unicorn::try_new()?, cli.brightness)?,
         None => {
             log::info!("Defaulting to Unicorn mode");
-            go(Unicorn::new())?
+            go(Unicorn::try_new()?, cli.brightness)?
         },
     }
 
@@ -0,0 +1,49 @@
+use std::{path::PathBuf, time::Duration};
+
+use clap::Parser;
+use color_eyre::Result;
+use unicorn::{
+    emulator::terminal::TerminalDisplay,
+    image::{Animation, Fit, PlayMode},
+    pimoroni::{unicorn::Unicorn, unicornmini::UnicornMini, Display},
+};
+
+#[derive(Parser, Clone)]
+enum Mode {
+    UnicornMini,
+    Unicorn,
+    Terminal,
+}
+
+#[derive(Parser)]
+struct Cli {
+    #[clap(subcommand)]
+    mode: Mode,
+    /// Time to show each still image for, when playing several
+    #[clap(long, default_value = "100")]
+    delay_ms: u64,
+    /// PNG or GIF to show, or several to play in turn
+    #[clap(required = true)]
+    paths: Vec<PathBuf>,
+}
+
+// Play a PNG, an animated GIF or a sequence of them, bouncing back and forth
+// through the frames.
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let animation = Animation::open_sequence(&cli.paths, Duration::from_millis(cli.delay_ms))?;
+
+    match cli.mode {
+        Mode::UnicornMini => play(&animation, UnicornMini::try_new()?).await,
+        Mode::Unicorn => play(&animation, Unicorn::try_new()?).await,
+        Mode::Terminal => play(&animation, TerminalDisplay::unicorn()).await,
+    }
+}
+
+async fn play(animation: &Animation, display: impl Display + Send + 'static) -> Result<()> {
+    animation
+        .play(display, Fit::Cover, PlayMode::Bounce)
+        .await?;
+    Ok(())
+}
@@ -9,6 +9,8 @@ static GREEN: RGB8 = RGB8::new(0, 20, 0);
 static BLUE: RGB8 = RGB8::new(0, 0, 20);
 static BLACK: RGB8 = RGB8::new(0, 0, 0);
 
+// Voltage is only ever logged via Debug
+#[allow(dead_code)]
 #[derive(Debug)]
 enum State {
     Live { percentage: f32, voltage: f32 },
@@ -122,7 +124,7 @@ fn go<T: Display>(mut display: T) -> Result<()> {
         for (idx, rgb) in px.iter().enumerate() {
             display.set_idx(idx, rgb);
         }
-        display.flush();
+        display.flush()?;
 
         std::thread::sleep(Duration::from_millis(1000));
     }
@@ -138,8 +140,8 @@ fn main() -> Result<()> {
     env_logger::init();
 
     match Mode::parse() {
-        Mode::UnicornMini => go(UnicornMini::new())?,
-        Mode::Unicorn => go(Unicorn::new())?,
+        Mode::UnicornMini => go(UnicornMini::try_new()?)?,
+        Mode::Unicorn => go(Unicorn::try_new()?)?,
     };
 
     Ok(())
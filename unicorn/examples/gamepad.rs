@@ -0,0 +1,59 @@
+use color_eyre::eyre::Result;
+use rgb::RGB8;
+use unicorn::{
+    gamepad::{capture_gamepads, Axis, GamepadEvent, GamepadOptions, Input},
+    pimoroni::{unicorn::Unicorn, Display},
+};
+
+// Steer a dot around the HD with the left stick or d-pad of any gamepad.
+#[tokio::main]
+async fn main() -> Result<()> {
+    color_eyre::install()?;
+
+    let mut display = Unicorn::try_new()?;
+    let dims = *display.dimensions();
+    let mut gamepads = capture_gamepads(GamepadOptions::default())?;
+
+    let (mut x, mut y) = (dims.width / 2, dims.height / 2);
+    display.set_xy(x, y, &RGB8::new(255, 255, 255));
+    display.flush()?;
+
+    while let Some(event) = gamepads.next().await {
+        let input = match event {
+            GamepadEvent::Input(event) => event.input,
+            GamepadEvent::DeviceAdded(info) => {
+                println!("Added {}", info.name);
+                continue;
+            }
+            GamepadEvent::DeviceRemoved(info) => {
+                println!("Removed {}", info.name);
+                continue;
+            }
+        };
+
+        let step = |value: f32| match value {
+            v if v < -0.5 => -1,
+            v if v > 0.5 => 1,
+            _ => 0,
+        };
+        let (dx, dy) = match input {
+            Input::Axis {
+                axis: Axis::LeftX | Axis::DpadX,
+                value,
+            } => (step(value), 0),
+            Input::Axis {
+                axis: Axis::LeftY | Axis::DpadY,
+                value,
+            } => (0, step(value)),
+            _ => continue,
+        };
+
+        display.set_xy(x, y, &RGB8::default());
+        x = x.saturating_add_signed(dx).min(dims.width - 1);
+        y = y.saturating_add_signed(dy).min(dims.height - 1);
+        display.set_xy(x, y, &RGB8::new(255, 255, 255));
+        display.flush()?;
+    }
+
+    Ok(())
+}
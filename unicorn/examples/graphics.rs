@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+use color_eyre::Result;
+use embedded_graphics::{
+    pixelcolor::Rgb888,
+    prelude::{Point, Primitive, RgbColor, Size},
+    primitives::{Circle, PrimitiveStyle, Rectangle},
+    Drawable,
+};
+use unicorn::{emulator::terminal::TerminalDisplay, graphics::Graphics, pimoroni::Display};
+
+// Bounce a circle around an emulated Unicorn HAT HD.
+fn main() -> Result<()> {
+    let mut display = TerminalDisplay::unicorn();
+    let (mut x, mut y, mut dx, mut dy) = (2, 5, 1, 1);
+
+    loop {
+        let mut canvas = Graphics::new(&mut display);
+        Rectangle::new(Point::zero(), Size::new(16, 16))
+            .into_styled(PrimitiveStyle::with_fill(Rgb888::BLACK))
+            .draw(&mut canvas)?;
+        Circle::new(Point::new(x, y), 5)
+            .into_styled(PrimitiveStyle::with_stroke(Rgb888::CYAN, 1))
+            .draw(&mut canvas)?;
+        display.flush()?;
+
+        if !(0..11).contains(&(x + dx)) {
+            dx = -dx;
+        }
+        if !(0..11).contains(&(y + dy)) {
+            dy = -dy;
+        }
+        x += dx;
+        y += dy;
+
+        std::thread::sleep(Duration::from_millis(80));
+    }
+}
@@ -0,0 +1,31 @@
+use color_eyre::eyre::Result;
+use unicorn::keyboard::{capture_keyboards, CaptureEvent, CaptureOptions, Key, KeyState, Keymap};
+
+// Echo what's typed on any keyboard, until Esc is pressed.
+#[tokio::main]
+async fn main() -> Result<()> {
+    color_eyre::install()?;
+
+    let mut keyboards = capture_keyboards(CaptureOptions {
+        keymap: Some(Keymap::Uk),
+        ..Default::default()
+    })?;
+
+    while let Some(event) = keyboards.next().await {
+        match event {
+            CaptureEvent::Key(event) if event.key == Key::Esc => break,
+            CaptureEvent::Key(event) => match event.text {
+                Some(c) => print!("{}", c),
+                None if event.state == KeyState::Pressed => {
+                    println!("[{:?} from {}]", event.key, event.device)
+                }
+                None => {}
+            },
+            CaptureEvent::DeviceAdded(info) => println!("[Added {}]", info.name),
+            CaptureEvent::DeviceRemoved(info) => println!("[Removed {}]", info.name),
+        }
+    }
+
+    keyboards.shutdown().await;
+    Ok(())
+}
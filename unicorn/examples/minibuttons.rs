@@ -1,27 +1,18 @@
-use std::time::Duration;
-
 use color_eyre::eyre::Result;
 use rand::Rng;
 use rgb::RGB8;
-use tokio::runtime::Runtime;
 use unicorn::pimoroni::{
-    unicornmini::{Button, UnicornMini},
+    unicornmini::{EventKind, UnicornMini},
     Display,
 };
 
-fn main() -> Result<()> {
+#[tokio::main]
+async fn main() -> Result<()> {
     color_eyre::install()?;
-
-    let _ = tokio::runtime::Builder::new_multi_thread()
-        .enable_all()
-        .build()
-        .unwrap()
-        .block_on(async { go().await })?;
-
-    Ok(())
+    go().await
 }
 
-fn fill_with_random_colour(um: &mut UnicornMini, rng: &mut impl Rng) {
+fn fill_with_random_colour(um: &mut UnicornMini, rng: &mut impl Rng) -> Result<()> {
     let r = rng.gen();
     let g = rng.gen();
     let b = rng.gen();
@@ -30,28 +21,26 @@ fn fill_with_random_colour(um: &mut UnicornMini, rng: &mut impl Rng) {
             um.set_xy(i, j, &RGB8::new(r, g, b));
         }
     }
-    um.flush();
+    um.flush()?;
+    Ok(())
 }
 
 async fn go() -> Result<()> {
-    let rt = Runtime::new().unwrap();
-    let mut um = UnicornMini::new();
+    let mut um = UnicornMini::try_new()?;
 
     let mut rng = rand::thread_rng();
 
-    let mut h = um.button_subscribe(&rt);
+    let mut buttons = um.buttons()?;
 
-    loop {
-        h.changed().await.unwrap();
-        let b_opt = h.borrow_and_update();
-        let t = b_opt.as_ref().unwrap();
-        println!("==> {:?}", t);
+    while let Some(event) = buttons.next().await {
+        println!("==> {:?} {:?}", event.button, event.kind);
 
-        match *t {
-            Button::A | Button::B | Button::X | Button::Y => {
-                fill_with_random_colour(&mut um, &mut rng);
-            }
+        match event.kind {
+            EventKind::Pressed => fill_with_random_colour(&mut um, &mut rng)?,
+            EventKind::LongPress => um.reset()?,
+            EventKind::Released | EventKind::DoubleClick => {}
         }
-        std::thread::sleep(Duration::from_millis(500));
     }
+
+    Ok(())
 }
@@ -0,0 +1,29 @@
+use clap::Parser;
+use color_eyre::Result;
+use tokio::net::TcpListener;
+use unicorn::{
+    emulator::terminal::TerminalDisplay,
+    net::opc::{OpcServer, DEFAULT_PORT},
+    pimoroni::{unicorn::Unicorn, Display},
+};
+
+#[derive(Parser, Clone)]
+enum Mode {
+    Unicorn,
+    Terminal,
+}
+
+// Show frames from OPC clients on a Unicorn HAT HD, or a terminal emulating one.
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::init();
+
+    let display: Box<dyn Display + Send> = match Mode::parse() {
+        Mode::Unicorn => Box::new(Unicorn::try_new()?),
+        Mode::Terminal => Box::new(TerminalDisplay::unicorn()),
+    };
+    let listener = TcpListener::bind(("0.0.0.0", DEFAULT_PORT)).await?;
+    OpcServer::new(display, 1).run(listener).await?;
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+use clap::Parser;
+use color_eyre::Result;
+use rgb::RGB8;
+use unicorn::{
+    emulator::terminal::TerminalDisplay,
+    pimoroni::{Dimensions, Display},
+};
+
+#[derive(Parser, Clone)]
+enum Mode {
+    UnicornMini,
+    Unicorn,
+}
+
+// Sweep a coloured column across an emulated board.
+fn main() -> Result<()> {
+    let dims = match Mode::parse() {
+        Mode::UnicornMini => Dimensions::UNICORN_MINI,
+        Mode::Unicorn => Dimensions::UNICORN,
+    };
+    let mut display = TerminalDisplay::new(dims);
+
+    for step in 0.. {
+        let column = step % dims.width;
+        for x in 0..dims.width {
+            for y in 0..dims.height {
+                let rgb = if x == column {
+                    RGB8::new(0, (y * 255 / dims.height) as u8, 200)
+                } else {
+                    RGB8::new(0, 0, 0)
+                };
+                display.set_xy(x, y, &rgb);
+            }
+        }
+        display.flush()?;
+
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,28 @@
+use clap::Parser;
+use color_eyre::Result;
+use unicorn::{
+    emulator::terminal::TerminalDisplay,
+    pimoroni::{unicornmini::UnicornMini, Display},
+    text::{ScrollSettings, TextScroller},
+};
+
+#[derive(Parser, Clone)]
+enum Mode {
+    UnicornMini,
+    Terminal,
+}
+
+// Scroll the hostname across a Unicorn Mini, or a terminal emulating one.
+#[tokio::main]
+async fn main() -> Result<()> {
+    let hostname = std::fs::read_to_string("/proc/sys/kernel/hostname")?;
+    let mut scroller = TextScroller::new(hostname.trim(), ScrollSettings::default());
+
+    let mut display: Box<dyn Display> = match Mode::parse() {
+        Mode::UnicornMini => Box::new(UnicornMini::try_new()?),
+        Mode::Terminal => Box::new(TerminalDisplay::unicorn_mini()),
+    };
+    scroller.run(display.as_mut()).await?;
+
+    Ok(())
+}
@@ -0,0 +1,29 @@
+// Enums for Linux input event codes, from linux/input-event-codes.h
+
+macro_rules! codes {
+    ($(#[$outer:meta])* $name:ident { $($(#[$doc:meta])* $variant:ident = $code:literal,)* }) => {
+        $(#[$outer])*
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($(#[$doc])* $variant,)*
+            /// Any code without a name here.
+            Other(u16),
+        }
+
+        impl $name {
+            pub fn from_code(code: u16) -> Self {
+                match code {
+                    $($code => $name::$variant,)*
+                    code => $name::Other(code),
+                }
+            }
+
+            pub fn code(&self) -> u16 {
+                match self {
+                    $($name::$variant => $code,)*
+                    $name::Other(code) => *code,
+                }
+            }
+        }
+    };
+}
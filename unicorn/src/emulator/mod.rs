@@ -0,0 +1,70 @@
+// Displays which stand in for a HAT when developing away from a Pi.
+
+use rgb::RGB8;
+
+use crate::pimoroni::{
+    pixelmap::{Grid, PixelMap},
+    Dimensions,
+};
+
+pub mod snapshot;
+pub mod terminal;
+
+/// In-memory copy of a board's LEDs, stored row by row.
+///
+/// Pixel indexes follow the board with the same geometry, as in
+/// [`Grid::for_dimensions`].
+pub(crate) struct Framebuffer {
+    dims: Dimensions,
+    map: Grid,
+    pixels: Vec<RGB8>,
+    brightness: f32,
+}
+impl Framebuffer {
+    pub fn new(dims: Dimensions) -> Self {
+        Framebuffer {
+            dims,
+            map: Grid::for_dimensions(dims),
+            pixels: vec![RGB8::default(); dims.num_px()],
+            brightness: 1.0,
+        }
+    }
+
+    pub fn set_xy(&mut self, x: usize, y: usize, rgb: &RGB8) {
+        assert!(x < self.dims.width, "LED x index out of range: {}", x);
+        assert!(y < self.dims.height, "LED y index out of range: {}", y);
+
+        self.pixels[x + y * self.dims.width] = *rgb;
+    }
+
+    pub fn set_idx(&mut self, idx: usize, rgb: &RGB8) {
+        let Some((x, y)) = self.map.position(idx) else {
+            panic!("LED index out of range: {}", idx);
+        };
+
+        self.pixels[x + y * self.dims.width] = *rgb;
+    }
+
+    /// Colour of the LED at `x`, `y` as it would appear, i.e. after brightness scaling.
+    pub fn get(&self, x: usize, y: usize) -> RGB8 {
+        let scale = |v: u8| (v as f32 * self.brightness).round() as u8;
+        let px = self.pixels[x + y * self.dims.width];
+        RGB8::new(scale(px.r), scale(px.g), scale(px.b))
+    }
+
+    pub fn set_brightness(&mut self, brightness: f32) {
+        self.brightness = brightness.clamp(0.0, 1.0);
+    }
+
+    pub fn brightness(&self) -> f32 {
+        self.brightness
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels.fill(RGB8::default());
+    }
+
+    pub fn dimensions(&self) -> &Dimensions {
+        &self.dims
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use rgb::RGB8;
+
+use super::Framebuffer;
+use crate::{
+    error::Error,
+    pimoroni::{Dimensions, Display},
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Ppm,
+}
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Ppm => "ppm",
+        }
+    }
+}
+
+/// Headless display which encodes each flushed frame as an image, drawing
+/// every LED as a `scale` x `scale` block.
+pub struct SnapshotDisplay {
+    frame: Framebuffer,
+    scale: usize,
+    format: ImageFormat,
+    dir: Option<PathBuf>,
+    frames_flushed: usize,
+    last_image: Option<Vec<u8>>,
+}
+
+impl SnapshotDisplay {
+    /// Keep flushed frames in memory only; see [`SnapshotDisplay::last_image`].
+    pub fn new(dims: Dimensions, scale: usize, format: ImageFormat) -> Self {
+        assert!(scale > 0, "Snapshot scale must be at least 1");
+        SnapshotDisplay {
+            frame: Framebuffer::new(dims),
+            scale,
+            format,
+            dir: None,
+            frames_flushed: 0,
+            last_image: None,
+        }
+    }
+
+    /// Also write every flushed frame to `dir` as `frame-00000.png`,
+    /// `frame-00001.png` and so on.
+    pub fn writing_to(
+        dims: Dimensions,
+        scale: usize,
+        format: ImageFormat,
+        dir: impl AsRef<Path>,
+    ) -> Result<Self, Error> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir).map_err(Error::Io)?;
+
+        let mut display = Self::new(dims, scale, format);
+        display.dir = Some(dir.to_owned());
+        Ok(display)
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> RGB8 {
+        self.frame.get(x, y)
+    }
+
+    pub fn frames_flushed(&self) -> usize {
+        self.frames_flushed
+    }
+
+    /// The image produced by the most recent flush.
+    pub fn last_image(&self) -> Option<&[u8]> {
+        self.last_image.as_deref()
+    }
+
+    /// Encode the current contents of the framebuffer, flushed or not.
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        let (width, height, rgb) = self.scaled_rgb();
+
+        match self.format {
+            ImageFormat::Ppm => {
+                let mut out = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+                out.extend(rgb);
+                Ok(out)
+            }
+            ImageFormat::Png => {
+                let mut out = Vec::new();
+                let mut encoder = png::Encoder::new(&mut out, width as u32, height as u32);
+                encoder.set_color(png::ColorType::Rgb);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder
+                    .write_header()
+                    .and_then(|mut writer| writer.write_image_data(&rgb))
+                    .map_err(|e| Error::Io(io::Error::other(e)))?;
+                Ok(out)
+            }
+        }
+    }
+
+    fn scaled_rgb(&self) -> (usize, usize, Vec<u8>) {
+        let Dimensions { width, height } = *self.frame.dimensions();
+        let (scaled_width, scaled_height) = (width * self.scale, height * self.scale);
+
+        let mut rgb = Vec::with_capacity(scaled_width * scaled_height * 3);
+        for y in 0..scaled_height {
+            for x in 0..scaled_width {
+                let px = self.frame.get(x / self.scale, y / self.scale);
+                rgb.extend([px.r, px.g, px.b]);
+            }
+        }
+
+        (scaled_width, scaled_height, rgb)
+    }
+}
+
+impl Display for SnapshotDisplay {
+    fn set_xy(&mut self, x: usize, y: usize, rgb: &RGB8) {
+        self.frame.set_xy(x, y, rgb);
+    }
+
+    fn set_idx(&mut self, idx: usize, rgb: &RGB8) {
+        self.frame.set_idx(idx, rgb);
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        let image = self.encode()?;
+
+        if let Some(dir) = &self.dir {
+            let file_name = format!(
+                "frame-{:05}.{}",
+                self.frames_flushed,
+                self.format.extension()
+            );
+            fs::write(dir.join(file_name), &image).map_err(Error::Io)?;
+        }
+
+        self.frames_flushed += 1;
+        self.last_image = Some(image);
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        self.frame.clear();
+        self.flush()
+    }
+
+    fn dimensions(&self) -> &Dimensions {
+        self.frame.dimensions()
+    }
+
+    fn set_brightness(&mut self, brightness: f32) {
+        self.frame.set_brightness(brightness);
+    }
+
+    fn brightness(&self) -> f32 {
+        self.frame.brightness()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ppm_scales_each_led_to_a_block() {
+        let dims = Dimensions {
+            width: 2,
+            height: 1,
+        };
+        let mut display = SnapshotDisplay::new(dims, 2, ImageFormat::Ppm);
+        display.set_xy(1, 0, &RGB8::new(1, 2, 3));
+        display.flush().unwrap();
+
+        let mut expected = b"P6\n4 2\n255\n".to_vec();
+        for _ in 0..2 {
+            expected.extend([0, 0, 0, 0, 0, 0, 1, 2, 3, 1, 2, 3]);
+        }
+        assert_eq!(display.last_image().unwrap(), expected.as_slice());
+    }
+
+    #[test]
+    fn test_png_round_trip() {
+        let mut display = SnapshotDisplay::new(Dimensions::UNICORN_MINI, 3, ImageFormat::Png);
+        display.set_xy(16, 6, &RGB8::new(10, 20, 30));
+        display.flush().unwrap();
+
+        let decoder = png::Decoder::new(display.last_image().unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+
+        assert_eq!((info.width, info.height), (51, 21));
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+        let last = buf.len() - 3;
+        assert_eq!(buf[last..], [10, 20, 30]);
+        assert_eq!(buf[..3], [0, 0, 0]);
+    }
+
+    #[test]
+    fn test_writes_numbered_frames() {
+        let dir = std::env::temp_dir().join(format!("unicorn-snapshot-{}", std::process::id()));
+        let mut display =
+            SnapshotDisplay::writing_to(Dimensions::UNICORN, 1, ImageFormat::Ppm, &dir).unwrap();
+        display.flush().unwrap();
+        display.set_idx(0, &RGB8::new(1, 1, 1));
+        display.flush().unwrap();
+
+        assert_eq!(display.frames_flushed(), 2);
+        assert!(dir.join("frame-00000.ppm").exists());
+        let second = fs::read(dir.join("frame-00001.ppm")).unwrap();
+        assert_eq!(second, display.last_image().unwrap());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "LED index out of range")]
+    fn test_set_idx_out_of_range() {
+        let mut display = SnapshotDisplay::new(Dimensions::UNICORN, 1, ImageFormat::Png);
+        display.set_idx(256, &RGB8::new(1, 1, 1));
+    }
+
+    #[test]
+    fn test_brightness_scales_rendered_pixels() {
+        let mut display = SnapshotDisplay::new(Dimensions::UNICORN, 1, ImageFormat::Ppm);
+        display.set_xy(0, 0, &RGB8::new(200, 100, 0));
+        display.set_brightness(0.25);
+
+        assert_eq!(display.pixel(0, 0), RGB8::new(50, 25, 0));
+    }
+}
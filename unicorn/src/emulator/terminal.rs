@@ -0,0 +1,187 @@
+use std::io::{self, Stdout, Write};
+
+use rgb::RGB8;
+
+use super::Framebuffer;
+use crate::{
+    error::Error,
+    pimoroni::{Dimensions, Display},
+};
+
+// Each character cell shows two LEDs: the upper half block is drawn in the
+// top LED's colour over a background of the LED below it.
+const UPPER_HALF_BLOCK: char = '\u{2580}';
+
+/// Renders the display to a 24-bit colour terminal, redrawing in place on
+/// every flush.
+pub struct TerminalDisplay<W: Write = Stdout> {
+    out: W,
+    frame: Framebuffer,
+    lines_drawn: usize,
+}
+
+impl TerminalDisplay {
+    pub fn new(dims: Dimensions) -> Self {
+        Self::with_writer(dims, io::stdout())
+    }
+
+    pub fn unicorn() -> Self {
+        Self::new(Dimensions::UNICORN)
+    }
+
+    pub fn unicorn_mini() -> Self {
+        Self::new(Dimensions::UNICORN_MINI)
+    }
+}
+
+impl<W: Write> TerminalDisplay<W> {
+    pub fn with_writer(dims: Dimensions, out: W) -> Self {
+        TerminalDisplay {
+            out,
+            frame: Framebuffer::new(dims),
+            lines_drawn: 0,
+        }
+    }
+
+    pub fn writer(&self) -> &W {
+        &self.out
+    }
+
+    fn render(&self) -> String {
+        fn fg(rgb: RGB8) -> String {
+            format!("\x1b[38;2;{};{};{}m", rgb.r, rgb.g, rgb.b)
+        }
+        fn bg(rgb: RGB8) -> String {
+            format!("\x1b[48;2;{};{};{}m", rgb.r, rgb.g, rgb.b)
+        }
+
+        let Dimensions { width, height } = *self.frame.dimensions();
+        let mut s = String::new();
+
+        if self.lines_drawn > 0 {
+            s.push_str(&format!("\x1b[{}A", self.lines_drawn));
+        }
+
+        for y in (0..height).step_by(2) {
+            for x in 0..width {
+                s.push_str(&fg(self.frame.get(x, y)));
+                if y + 1 < height {
+                    s.push_str(&bg(self.frame.get(x, y + 1)));
+                } else {
+                    s.push_str("\x1b[49m");
+                }
+                s.push(UPPER_HALF_BLOCK);
+            }
+            s.push_str("\x1b[0m\n");
+        }
+
+        s
+    }
+}
+
+impl<W: Write> Drop for TerminalDisplay<W> {
+    fn drop(&mut self) {
+        self.reset().ok();
+    }
+}
+
+impl<W: Write> Display for TerminalDisplay<W> {
+    fn set_xy(&mut self, x: usize, y: usize, rgb: &RGB8) {
+        self.frame.set_xy(x, y, rgb);
+    }
+
+    fn set_idx(&mut self, idx: usize, rgb: &RGB8) {
+        self.frame.set_idx(idx, rgb);
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        let rendered = self.render();
+        self.out
+            .write_all(rendered.as_bytes())
+            .and_then(|_| self.out.flush())
+            .map_err(Error::Io)?;
+        self.lines_drawn = self.frame.dimensions().height.div_ceil(2);
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        self.frame.clear();
+        self.flush()
+    }
+
+    fn dimensions(&self) -> &Dimensions {
+        self.frame.dimensions()
+    }
+
+    fn set_brightness(&mut self, brightness: f32) {
+        self.frame.set_brightness(brightness);
+    }
+
+    fn brightness(&self) -> f32 {
+        self.frame.brightness()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(display: &TerminalDisplay<Vec<u8>>) -> String {
+        String::from_utf8(display.writer().clone()).unwrap()
+    }
+
+    #[test]
+    fn test_render_pairs_rows_into_half_blocks() {
+        let dims = Dimensions {
+            width: 2,
+            height: 3,
+        };
+        let mut display = TerminalDisplay::with_writer(dims, Vec::new());
+        display.set_xy(0, 0, &RGB8::new(1, 2, 3));
+        display.set_xy(0, 1, &RGB8::new(4, 5, 6));
+        display.set_xy(1, 2, &RGB8::new(7, 8, 9));
+        display.flush().unwrap();
+
+        let expected = "\x1b[38;2;1;2;3m\x1b[48;2;4;5;6m▀\
+                        \x1b[38;2;0;0;0m\x1b[48;2;0;0;0m▀\x1b[0m\n\
+                        \x1b[38;2;0;0;0m\x1b[49m▀\
+                        \x1b[38;2;7;8;9m\x1b[49m▀\x1b[0m\n";
+        assert_eq!(output(&display), expected);
+    }
+
+    #[test]
+    fn test_flush_redraws_in_place() {
+        let mut display = TerminalDisplay::with_writer(Dimensions::UNICORN_MINI, Vec::new());
+        display.flush().unwrap();
+        let first = output(&display);
+        display.flush().unwrap();
+
+        let second = &output(&display)[first.len()..];
+        assert!(!first.starts_with("\x1b[4A"));
+        assert!(second.starts_with("\x1b[4A"));
+        assert_eq!(first, second["\x1b[4A".len()..]);
+    }
+
+    #[test]
+    fn test_mini_indexes_are_column_major() {
+        let mut display = TerminalDisplay::with_writer(Dimensions::UNICORN_MINI, Vec::new());
+        display.set_idx(8, &RGB8::new(1, 1, 1));
+
+        assert_eq!(display.frame.get(1, 1), RGB8::new(1, 1, 1));
+    }
+
+    #[test]
+    fn test_hd_indexes_are_row_major() {
+        let mut display = TerminalDisplay::with_writer(Dimensions::UNICORN, Vec::new());
+        display.set_idx(17, &RGB8::new(1, 1, 1));
+
+        assert_eq!(display.frame.get(1, 1), RGB8::new(1, 1, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "LED x index out of range")]
+    fn test_set_xy_out_of_range() {
+        let mut display = TerminalDisplay::with_writer(Dimensions::UNICORN_MINI, Vec::new());
+        display.set_xy(17, 0, &RGB8::new(1, 1, 1));
+    }
+}
@@ -1,4 +1,4 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, io};
 
 pub type BoxedError = Box<dyn std::error::Error + Sync + Send>;
 
@@ -15,3 +15,127 @@ impl std::fmt::Display for AppError {
     }
 }
 impl std::error::Error for AppError {}
+
+/// Failures talking to the display and button hardware.
+#[derive(Debug)]
+pub enum Error {
+    /// The device node exists but the current user may not open it.
+    PermissionDenied {
+        path: String,
+    },
+    /// The device node could not be opened, e.g. because SPI is not enabled.
+    DeviceUnavailable {
+        path: String,
+        source: io::Error,
+    },
+    SpiConfig {
+        path: String,
+        source: io::Error,
+    },
+    SpiWrite(io::Error),
+    /// The transport accepted fewer bytes than it was given.
+    ShortWrite {
+        expected: usize,
+        written: usize,
+    },
+    GpioUnavailable(rppal::gpio::Error),
+    /// Failure writing to a non-SPI output, such as an emulator's terminal.
+    Io(io::Error),
+    /// An image or animation could not be decoded.
+    Decode(BoxedError),
+    IndexOutOfRange {
+        idx: usize,
+        num_px: usize,
+    },
+    CoordinateOutOfRange {
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    },
+    /// A pixel map file could not be understood.
+    InvalidPixelMap(String),
+    /// A receiver would need universes beyond those its protocol allows.
+    UniverseOutOfRange {
+        first: u16,
+        count: usize,
+        max: u16,
+    },
+}
+impl Error {
+    /// Classify a failure to open the device node at `path`.
+    pub fn open(path: &str, source: io::Error) -> Self {
+        match source.kind() {
+            io::ErrorKind::PermissionDenied => Error::PermissionDenied {
+                path: path.to_owned(),
+            },
+            _ => Error::DeviceUnavailable {
+                path: path.to_owned(),
+                source,
+            },
+        }
+    }
+}
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::PermissionDenied { path } => {
+                write!(f, "Do you have sufficient permissions to '{}' ?", path)
+            }
+            Error::DeviceUnavailable { path, source } => {
+                write!(f, "Failed to open '{}': {}", path, source)
+            }
+            Error::SpiConfig { path, source } => {
+                write!(f, "SPI config error on '{}': {}", path, source)
+            }
+            Error::SpiWrite(source) => write!(f, "SPI write error: {}", source),
+            Error::ShortWrite { expected, written } => write!(
+                f,
+                "Short SPI write: {} of {} bytes written",
+                written, expected
+            ),
+            Error::GpioUnavailable(source) => write!(f, "GPIO unavailable: {}", source),
+            Error::Io(source) => write!(f, "I/O error: {}", source),
+            Error::Decode(source) => write!(f, "Failed to decode image: {}", source),
+            Error::IndexOutOfRange { idx, num_px } => write!(
+                f,
+                "LED index out of range: {} (display has {} pixels)",
+                idx, num_px
+            ),
+            Error::CoordinateOutOfRange {
+                x,
+                y,
+                width,
+                height,
+            } => write!(
+                f,
+                "LED coordinate out of range: ({}, {}) on a {}x{} display",
+                x, y, width, height
+            ),
+            Error::InvalidPixelMap(reason) => write!(f, "Invalid pixel map: {}", reason),
+            Error::UniverseOutOfRange { first, count, max } => write!(
+                f,
+                "{} universes from {} run past the last universe, {}",
+                count, first, max
+            ),
+        }
+    }
+}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::DeviceUnavailable { source, .. } | Error::SpiConfig { source, .. } => {
+                Some(source)
+            }
+            Error::SpiWrite(source) | Error::Io(source) => Some(source),
+            Error::GpioUnavailable(source) => Some(source),
+            Error::Decode(source) => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+impl From<rppal::gpio::Error> for Error {
+    fn from(e: rppal::gpio::Error) -> Self {
+        Error::GpioUnavailable(e)
+    }
+}
@@ -0,0 +1,423 @@
+// Gamepads, joysticks and arcade encoders, captured the same way as keyboards.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use libc::input_event;
+
+use crate::{
+    error::Error,
+    keyboard::{
+        capture::{Capture, Source},
+        DeviceMatch, InputDevice, InputDeviceInfo,
+    },
+};
+
+const EV_KEY: u16 = 0x01;
+const EV_ABS: u16 = 0x03;
+
+codes! {
+    /// A gamepad button, by its Linux `BTN_*` code. Joysticks and arcade
+    /// encoders number their buttons from `Trigger` instead.
+    Button {
+        Trigger = 0x120,
+        Thumb = 0x121,
+        Thumb2 = 0x122,
+        Top = 0x123,
+        Top2 = 0x124,
+        Pinkie = 0x125,
+        Base = 0x126,
+        Base2 = 0x127,
+        Base3 = 0x128,
+        Base4 = 0x129,
+        Base5 = 0x12a,
+        Base6 = 0x12b,
+        /// A on Xbox-style pads, cross on PlayStation ones.
+        South = 0x130,
+        East = 0x131,
+        C = 0x132,
+        North = 0x133,
+        West = 0x134,
+        Z = 0x135,
+        LeftShoulder = 0x136,
+        RightShoulder = 0x137,
+        /// Only on pads whose triggers are buttons rather than axes.
+        LeftTrigger = 0x138,
+        RightTrigger = 0x139,
+        Select = 0x13a,
+        Start = 0x13b,
+        Mode = 0x13c,
+        LeftThumb = 0x13d,
+        RightThumb = 0x13e,
+        DpadUp = 0x220,
+        DpadDown = 0x221,
+        DpadLeft = 0x222,
+        DpadRight = 0x223,
+    }
+}
+
+codes! {
+    /// An axis, by its Linux `ABS_*` code.
+    Axis {
+        LeftX = 0x00,
+        LeftY = 0x01,
+        /// The left trigger on most pads.
+        LeftZ = 0x02,
+        RightX = 0x03,
+        RightY = 0x04,
+        /// The right trigger on most pads.
+        RightZ = 0x05,
+        Throttle = 0x06,
+        Rudder = 0x07,
+        /// The accelerator pedal or trigger of racing wheels and some pads.
+        Gas = 0x09,
+        Brake = 0x0a,
+        DpadX = 0x10,
+        DpadY = 0x11,
+    }
+}
+
+impl Axis {
+    /// Whether the axis is usually a trigger. Generic pads use Z and RZ for
+    /// a second stick too, so this is only a hint.
+    fn may_be_trigger(&self) -> bool {
+        matches!(self, Axis::LeftZ | Axis::RightZ | Axis::Gas | Axis::Brake)
+    }
+}
+
+/// The range an axis reports, from `EVIOCGABS`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AxisRange {
+    pub min: i32,
+    pub max: i32,
+    /// Values this close to the centre are treated as the centre.
+    pub flat: i32,
+    /// Whether the axis rests at one end, like a trigger, rather than in
+    /// the middle like a stick.
+    pub one_sided: bool,
+}
+
+impl AxisRange {
+    /// Pads differ over which axes are triggers, so one which may be is
+    /// taken to be one if it was nearer its minimum than its centre when the
+    /// device was captured. A trigger held more than half way down then is
+    /// taken for a stick.
+    fn new(axis: Axis, abs: &libc::input_absinfo) -> Self {
+        let centre = (abs.minimum as i64 + abs.maximum as i64) / 2;
+        let resting_low = (abs.value as i64) < centre - abs.flat as i64;
+        AxisRange {
+            min: abs.minimum,
+            max: abs.maximum,
+            flat: abs.flat,
+            one_sided: axis.may_be_trigger() && resting_low,
+        }
+    }
+
+    /// Scale `value` to -1.0 to 1.0, or 0.0 to 1.0 for a one-sided axis,
+    /// with up and left negative.
+    fn normalise(&self, value: i32) -> f32 {
+        if self.max <= self.min {
+            return 0.0;
+        }
+        let value = value.clamp(self.min, self.max);
+        let span = (self.max - self.min) as f32;
+
+        if self.one_sided {
+            return (value - self.min) as f32 / span;
+        }
+
+        let centre = self.min as f32 + span / 2.0;
+        if (value as f32 - centre).abs() <= self.flat as f32 {
+            return 0.0;
+        }
+        (2.0 * (value as f32 - centre) / span).clamp(-1.0, 1.0)
+    }
+}
+
+/// A button or axis changing.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Input {
+    Button { button: Button, pressed: bool },
+    Axis { axis: Axis, value: f32 },
+}
+
+#[derive(Clone, Debug)]
+pub struct InputEvent {
+    pub input: Input,
+    /// Name the gamepad reports for itself.
+    pub device: String,
+    pub time: SystemTime,
+}
+
+/// Something that happened to the captured gamepads.
+#[derive(Clone, Debug)]
+pub enum GamepadEvent {
+    Input(InputEvent),
+    /// A device has been captured, either at the start or when plugged in.
+    DeviceAdded(InputDeviceInfo),
+    /// A captured device has been unplugged.
+    DeviceRemoved(InputDeviceInfo),
+}
+
+/// Which gamepads to capture and how.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GamepadOptions {
+    /// Grab the devices with `EVIOCGRAB`, so nothing else sees their input.
+    /// Off by default, unlike for keyboards, since games and emulators on
+    /// the same machine usually want the pads too.
+    pub exclusive: bool,
+    /// Capture only devices matching one of these, whether or not they look
+    /// like gamepads. When empty, every gamepad is captured.
+    pub include: Vec<DeviceMatch>,
+    /// Never capture devices matching any of these.
+    pub exclude: Vec<DeviceMatch>,
+}
+
+impl GamepadOptions {
+    fn selects(&self, device: &InputDeviceInfo) -> bool {
+        if self.exclude.iter().any(|m| m.matches(device)) {
+            return false;
+        }
+
+        if self.include.is_empty() {
+            device.is_gamepad()
+        } else {
+            self.include.iter().any(|m| m.matches(device))
+        }
+    }
+}
+
+/// Captured gamepads.
+pub type Gamepads = Capture<GamepadEvent>;
+
+/// Capture the gamepads chosen by `options`, now and as they're plugged in,
+/// and report their buttons and axes and comings and goings.
+pub fn capture_gamepads(options: GamepadOptions) -> Result<Gamepads, Error> {
+    Capture::start(options)
+}
+
+/// Turns one gamepad's raw events into input events.
+pub(crate) struct GamepadDecoder {
+    device: String,
+    ranges: HashMap<u16, AxisRange>,
+}
+
+impl GamepadDecoder {
+    pub fn new(device: String, ranges: HashMap<u16, AxisRange>) -> Self {
+        GamepadDecoder { device, ranges }
+    }
+
+    pub fn decode(&mut self, event: &input_event) -> Option<InputEvent> {
+        let input = match event.type_ {
+            // Ignore autorepeat
+            EV_KEY if event.value < 2 => Input::Button {
+                button: Button::from_code(event.code),
+                pressed: event.value == 1,
+            },
+            EV_ABS => {
+                let range = self.ranges.get(&event.code)?;
+                Input::Axis {
+                    axis: Axis::from_code(event.code),
+                    value: range.normalise(event.value),
+                }
+            }
+            _ => return None,
+        };
+
+        let time = UNIX_EPOCH
+            + Duration::from_secs(event.time.tv_sec as u64)
+            + Duration::from_micros(event.time.tv_usec as u64);
+        Some(InputEvent {
+            input,
+            device: self.device.clone(),
+            time,
+        })
+    }
+}
+
+impl Source for GamepadOptions {
+    type Event = GamepadEvent;
+    type Decoder = GamepadDecoder;
+
+    const NAME: &'static str = "unicorn-gamepads";
+
+    fn selects(&self, device: &InputDeviceInfo) -> bool {
+        self.selects(device)
+    }
+
+    fn exclusive(&self) -> bool {
+        self.exclusive
+    }
+
+    fn decoder(&self, info: &InputDeviceInfo, device: &InputDevice) -> GamepadDecoder {
+        let name = device.name().unwrap_or_else(|_| info.name.clone());
+        let axes = info
+            .capability("ABS")
+            .map(|abs| abs.iter().collect::<Vec<_>>());
+
+        let mut ranges = HashMap::new();
+        for axis in axes.unwrap_or_default() {
+            match device.abs_info(axis as u16) {
+                Ok(abs) => {
+                    let range = AxisRange::new(Axis::from_code(axis as u16), &abs);
+                    ranges.insert(axis as u16, range);
+                }
+                Err(e) => log::warn!("No range for axis {} of {}: {}", axis, name, e),
+            }
+        }
+
+        GamepadDecoder::new(name, ranges)
+    }
+
+    fn decode(decoder: &mut GamepadDecoder, event: &input_event) -> Option<GamepadEvent> {
+        decoder.decode(event).map(GamepadEvent::Input)
+    }
+
+    fn added(info: InputDeviceInfo) -> GamepadEvent {
+        GamepadEvent::DeviceAdded(info)
+    }
+
+    fn removed(info: InputDeviceInfo) -> GamepadEvent {
+        GamepadEvent::DeviceRemoved(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(type_: u16, code: u16, value: i32) -> input_event {
+        input_event {
+            time: libc::timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+            type_,
+            code,
+            value,
+        }
+    }
+
+    #[test]
+    fn test_normalise() {
+        let stick = AxisRange {
+            min: -32768,
+            max: 32767,
+            flat: 128,
+            one_sided: false,
+        };
+        assert_eq!(stick.normalise(-32768), -1.0);
+        assert_eq!(stick.normalise(32767), 1.0);
+        assert_eq!(stick.normalise(100), 0.0);
+        assert!((stick.normalise(16384) - 0.5).abs() < 0.001);
+
+        // Cheap pads report sticks from 0, centred on 128
+        let byte = AxisRange {
+            min: 0,
+            max: 255,
+            flat: 15,
+            one_sided: false,
+        };
+        assert_eq!(byte.normalise(0), -1.0);
+        assert_eq!(byte.normalise(130), 0.0);
+        assert_eq!(byte.normalise(255), 1.0);
+
+        // Triggers rest at their minimum, wherever that is
+        let trigger = AxisRange {
+            one_sided: true,
+            ..byte
+        };
+        assert_eq!(trigger.normalise(0), 0.0);
+        assert_eq!(trigger.normalise(255), 1.0);
+        let trigger = AxisRange {
+            one_sided: true,
+            ..stick
+        };
+        assert_eq!(trigger.normalise(-32768), 0.0);
+        assert!((trigger.normalise(0) - 0.5).abs() < 0.001);
+
+        let hat = AxisRange {
+            min: -1,
+            max: 1,
+            flat: 0,
+            one_sided: false,
+        };
+        assert_eq!(hat.normalise(-1), -1.0);
+        assert_eq!(hat.normalise(0), 0.0);
+        assert_eq!(hat.normalise(1), 1.0);
+    }
+
+    #[test]
+    fn test_one_sided_axes() {
+        let abs = |value| libc::input_absinfo {
+            value,
+            minimum: 0,
+            maximum: 255,
+            fuzz: 0,
+            flat: 15,
+            resolution: 0,
+        };
+        let one_sided = |axis, value| AxisRange::new(axis, &abs(value)).one_sided;
+
+        assert!(one_sided(Axis::LeftZ, 0));
+        assert!(one_sided(Axis::Brake, 0));
+        // Half pressed when the pad was plugged in
+        assert!(one_sided(Axis::RightZ, 60));
+        // A second stick, as on generic pads
+        assert!(!one_sided(Axis::RightZ, 128));
+        // A stick held up when the pad was plugged in
+        assert!(!one_sided(Axis::LeftY, 0));
+    }
+
+    #[test]
+    fn test_decode() {
+        let trigger = AxisRange {
+            min: 0,
+            max: 1023,
+            flat: 0,
+            one_sided: true,
+        };
+        let mut decoder = GamepadDecoder::new("pad".to_string(), HashMap::from([(0x02, trigger)]));
+
+        let event = decoder.decode(&raw(EV_KEY, 0x130, 1)).unwrap();
+        assert_eq!(
+            event.input,
+            Input::Button {
+                button: Button::South,
+                pressed: true
+            }
+        );
+        assert_eq!(event.device, "pad");
+        assert_eq!(
+            decoder.decode(&raw(EV_KEY, 0x120, 0)).unwrap().input,
+            Input::Button {
+                button: Button::Trigger,
+                pressed: false
+            }
+        );
+        assert_eq!(
+            decoder.decode(&raw(EV_ABS, 0x02, 1023)).unwrap().input,
+            Input::Axis {
+                axis: Axis::LeftZ,
+                value: 1.0
+            }
+        );
+
+        // Axes without a known range, syncs and repeats are skipped
+        assert!(decoder.decode(&raw(EV_ABS, 0x00, 10)).is_none());
+        assert!(decoder.decode(&raw(0x00, 0, 0)).is_none());
+        assert!(decoder.decode(&raw(EV_KEY, 0x130, 2)).is_none());
+    }
+
+    #[test]
+    fn test_codes() {
+        for code in 0..0x300 {
+            assert_eq!(Button::from_code(code).code(), code);
+            assert_eq!(Axis::from_code(code).code(), code);
+        }
+        assert_eq!(Button::from_code(0x13b), Button::Start);
+        assert_eq!(Axis::from_code(0x11), Axis::DpadY);
+    }
+}
@@ -0,0 +1,108 @@
+use std::convert::Infallible;
+
+use embedded_graphics::{
+    pixelcolor::{Rgb888, RgbColor},
+    prelude::{DrawTarget, OriginDimensions, Pixel, Size},
+};
+use rgb::RGB8;
+
+use crate::pimoroni::Display;
+
+/// Lets any [`Display`] be drawn on with embedded-graphics.
+///
+/// A wrapper is needed as the orphan rule prevents implementing
+/// [`DrawTarget`] for every `T: Display` directly. Pixels falling off the
+/// display are clipped rather than tripping the bounds checks in `set_xy`.
+/// Drawing only updates the buffer; call [`Display::flush`] to show it.
+pub struct Graphics<'a, T: Display + ?Sized> {
+    display: &'a mut T,
+}
+
+impl<'a, T: Display + ?Sized> Graphics<'a, T> {
+    pub fn new(display: &'a mut T) -> Self {
+        Graphics { display }
+    }
+}
+
+impl<T: Display + ?Sized> OriginDimensions for Graphics<'_, T> {
+    fn size(&self) -> Size {
+        let dims = self.display.dimensions();
+        Size::new(dims.width as u32, dims.height as u32)
+    }
+}
+
+impl<T: Display + ?Sized> DrawTarget for Graphics<'_, T> {
+    type Color = Rgb888;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let dims = *self.display.dimensions();
+
+        for Pixel(point, colour) in pixels {
+            let (Ok(x), Ok(y)) = (usize::try_from(point.x), usize::try_from(point.y)) else {
+                continue;
+            };
+            if x < dims.width && y < dims.height {
+                let rgb = RGB8::new(colour.r(), colour.g(), colour.b());
+                self.display.set_xy(x, y, &rgb);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::{
+        prelude::{Point, Primitive},
+        primitives::{Line, PrimitiveStyle, Rectangle},
+        Drawable,
+    };
+
+    use super::*;
+    use crate::{
+        emulator::snapshot::{ImageFormat, SnapshotDisplay},
+        pimoroni::Dimensions,
+    };
+
+    fn display() -> SnapshotDisplay {
+        SnapshotDisplay::new(Dimensions::UNICORN_MINI, 1, ImageFormat::Ppm)
+    }
+
+    #[test]
+    fn test_size_matches_dimensions() {
+        let mut display = display();
+        assert_eq!(Graphics::new(&mut display).size(), Size::new(17, 7));
+    }
+
+    #[test]
+    fn test_draws_rectangle() {
+        let mut display = display();
+        Rectangle::new(Point::new(1, 1), Size::new(2, 2))
+            .into_styled(PrimitiveStyle::with_fill(Rgb888::RED))
+            .draw(&mut Graphics::new(&mut display))
+            .unwrap();
+
+        let red = RGB8::new(255, 0, 0);
+        assert_eq!(display.pixel(1, 1), red);
+        assert_eq!(display.pixel(2, 2), red);
+        assert_eq!(display.pixel(0, 0), RGB8::default());
+        assert_eq!(display.pixel(3, 3), RGB8::default());
+    }
+
+    #[test]
+    fn test_clips_off_display_pixels() {
+        let mut display = display();
+        Line::new(Point::new(-5, 3), Point::new(30, 3))
+            .into_styled(PrimitiveStyle::with_stroke(Rgb888::GREEN, 1))
+            .draw(&mut Graphics::new(&mut display))
+            .unwrap();
+
+        assert_eq!(display.pixel(0, 3), RGB8::new(0, 255, 0));
+        assert_eq!(display.pixel(16, 3), RGB8::new(0, 255, 0));
+    }
+}
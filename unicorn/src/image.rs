@@ -0,0 +1,573 @@
+use std::{fs, io, io::Read, path::Path, sync::Arc, time::Duration};
+
+use gif::{ColorOutput, DecodeOptions, DisposalMethod};
+use rgb::RGB8;
+
+use crate::{
+    error::Error,
+    pimoroni::{Dimensions, Display},
+};
+
+// Browsers treat very short GIF delays as this, so animations built for
+// them play at the intended speed.
+const DEFAULT_GIF_DELAY: Duration = Duration::from_millis(100);
+
+/// A still image has a single frame with no delay.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    /// Row by row, `width * height` pixels.
+    pub pixels: Vec<RGB8>,
+    pub delay: Duration,
+}
+
+/// How an image is fitted to a display of a different size.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Fit {
+    /// Scale each axis independently to fill the display.
+    Stretch,
+    /// Keep the aspect ratio, scaling to cover the display and cropping
+    /// whatever overhangs equally from both sides.
+    Cover,
+    /// Keep the original size, cropping or padding with black around the centre.
+    Centre,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PlayMode {
+    Once,
+    Loop,
+    /// Play forwards then backwards, repeatedly.
+    Bounce,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Animation {
+    width: usize,
+    height: usize,
+    frames: Vec<Frame>,
+}
+
+impl Animation {
+    /// Load a PNG or (possibly animated) GIF, detected from its contents.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let bytes = fs::read(path).map_err(Error::Io)?;
+
+        if bytes.starts_with(b"GIF8") {
+            Self::from_gif(bytes.as_slice())
+        } else {
+            Self::from_png(bytes.as_slice())
+        }
+    }
+
+    /// Load a sequence of images as the frames of one animation, each shown
+    /// for `delay`. Animated GIFs contribute all their frames, with their
+    /// own delays. Every image must be the same size.
+    pub fn open_sequence<P: AsRef<Path>>(
+        paths: impl IntoIterator<Item = P>,
+        delay: Duration,
+    ) -> Result<Self, Error> {
+        let mut size = None;
+        let mut frames = Vec::new();
+        for path in paths {
+            let path = path.as_ref();
+            let image = Self::open(path)?;
+            let (width, height) = *size.get_or_insert((image.width, image.height));
+            if (image.width, image.height) != (width, height) {
+                return Err(Error::Decode(
+                    format!(
+                        "{} is {}x{}, not {}x{} like the frames before it",
+                        path.display(),
+                        image.width,
+                        image.height,
+                        width,
+                        height
+                    )
+                    .into(),
+                ));
+            }
+
+            let still = image.frames.len() == 1;
+            frames.extend(image.frames.into_iter().map(|frame| Frame {
+                delay: if still { delay } else { frame.delay },
+                ..frame
+            }));
+        }
+
+        let (width, height) = size.ok_or_else(|| Error::Decode("No images given".into()))?;
+        Self::from_frames(width, height, frames)
+    }
+
+    /// Build an animation from frames of `width * height` pixels each.
+    pub fn from_frames(width: usize, height: usize, frames: Vec<Frame>) -> Result<Self, Error> {
+        if frames.is_empty() {
+            return Err(Error::Decode("Animation has no frames".into()));
+        }
+        if let Some((i, frame)) = frames
+            .iter()
+            .enumerate()
+            .find(|(_, frame)| frame.pixels.len() != width * height)
+        {
+            return Err(Error::Decode(
+                format!(
+                    "Frame {} has {} pixels, not {} for {}x{}",
+                    i,
+                    frame.pixels.len(),
+                    width * height,
+                    width,
+                    height
+                )
+                .into(),
+            ));
+        }
+
+        Ok(Animation {
+            width,
+            height,
+            frames,
+        })
+    }
+
+    pub fn from_png(reader: impl Read) -> Result<Self, Error> {
+        let mut decoder = png::Decoder::new(reader);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info().map_err(|e| Error::Decode(e.into()))?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader
+            .next_frame(&mut buf)
+            .map_err(|e| Error::Decode(e.into()))?;
+
+        let channels = info.color_type.samples();
+        let pixels = buf[..info.buffer_size()]
+            .chunks_exact(channels)
+            .map(|px| match px {
+                [l] => RGB8::new(*l, *l, *l),
+                [l, a] => over_black(RGB8::new(*l, *l, *l), *a),
+                [r, g, b] => RGB8::new(*r, *g, *b),
+                [r, g, b, a] => over_black(RGB8::new(*r, *g, *b), *a),
+                _ => unreachable!("PNG has {} channels", channels),
+            })
+            .collect();
+
+        Ok(Animation {
+            width: info.width as usize,
+            height: info.height as usize,
+            frames: vec![Frame {
+                pixels,
+                delay: Duration::ZERO,
+            }],
+        })
+    }
+
+    pub fn from_gif(reader: impl Read) -> Result<Self, Error> {
+        let mut options = DecodeOptions::new();
+        options.set_color_output(ColorOutput::RGBA);
+        let mut decoder = options
+            .read_info(reader)
+            .map_err(|e| Error::Decode(e.into()))?;
+
+        let (width, height) = (decoder.width() as usize, decoder.height() as usize);
+        // Frames may only cover part of the image, so build each one up on a
+        // canvas of RGBA pixels
+        let mut canvas = vec![[0u8; 4]; width * height];
+        let mut frames = Vec::new();
+
+        while let Some(frame) = decoder
+            .read_next_frame()
+            .map_err(|e| Error::Decode(e.into()))?
+        {
+            let previous = (frame.dispose == DisposalMethod::Previous).then(|| canvas.clone());
+            let rect = |i: usize| {
+                let x = frame.left as usize + i % frame.width as usize;
+                let y = frame.top as usize + i / frame.width as usize;
+                (x < width && y < height).then_some(x + y * width)
+            };
+
+            for (i, px) in frame.buffer.chunks_exact(4).enumerate() {
+                if let Some(c) = rect(i).filter(|_| px[3] > 0) {
+                    canvas[c].copy_from_slice(px);
+                }
+            }
+
+            let delay = Duration::from_millis(frame.delay as u64 * 10);
+            frames.push(Frame {
+                pixels: canvas
+                    .iter()
+                    .map(|[r, g, b, a]| over_black(RGB8::new(*r, *g, *b), *a))
+                    .collect(),
+                delay: if delay.is_zero() {
+                    DEFAULT_GIF_DELAY
+                } else {
+                    delay
+                },
+            });
+
+            match (frame.dispose, previous) {
+                (DisposalMethod::Background, _) => {
+                    for i in 0..frame.width as usize * frame.height as usize {
+                        if let Some(c) = rect(i) {
+                            canvas[c] = [0; 4];
+                        }
+                    }
+                }
+                (DisposalMethod::Previous, Some(previous)) => canvas = previous,
+                _ => {}
+            }
+        }
+
+        if frames.is_empty() {
+            return Err(Error::Decode("GIF has no frames".into()));
+        }
+
+        Ok(Animation {
+            width,
+            height,
+            frames,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// Resize every frame to `dims`, sampling the nearest pixel so pixel art
+    /// stays crisp.
+    pub fn fit(&self, dims: Dimensions, fit: Fit) -> Animation {
+        let (w, h) = (self.width as f32, self.height as f32);
+        let (dw, dh) = (dims.width as f32, dims.height as f32);
+
+        // Source pixels per display pixel, and the source position of the
+        // display's top left corner
+        let (scale_x, scale_y) = match fit {
+            Fit::Stretch => (w / dw, h / dh),
+            Fit::Cover => {
+                let scale = (w / dw).min(h / dh);
+                (scale, scale)
+            }
+            Fit::Centre => (1.0, 1.0),
+        };
+        let origin_x = (w - dw * scale_x) / 2.0;
+        let origin_y = (h - dh * scale_y) / 2.0;
+
+        let source = |x: usize, y: usize| {
+            let sx = (origin_x + (x as f32 + 0.5) * scale_x).floor();
+            let sy = (origin_y + (y as f32 + 0.5) * scale_y).floor();
+            (sx >= 0.0 && sy >= 0.0 && sx < w && sy < h)
+                .then(|| sx as usize + sy as usize * self.width)
+        };
+
+        let frames = self
+            .frames
+            .iter()
+            .map(|frame| {
+                let mut pixels = Vec::with_capacity(dims.num_px());
+                for y in 0..dims.height {
+                    for x in 0..dims.width {
+                        let px = source(x, y).map(|i| frame.pixels[i]);
+                        pixels.push(px.unwrap_or_default());
+                    }
+                }
+                Frame {
+                    pixels,
+                    delay: frame.delay,
+                }
+            })
+            .collect();
+
+        Animation {
+            width: dims.width,
+            height: dims.height,
+            frames,
+        }
+    }
+
+    /// Draw frame `idx` without flushing. The animation should already have
+    /// been fitted to the display; anything outside it is skipped.
+    pub fn draw<T: Display + ?Sized>(&self, display: &mut T, idx: usize) {
+        let dims = *display.dimensions();
+        let frame = &self.frames[idx];
+
+        for y in 0..self.height.min(dims.height) {
+            for x in 0..self.width.min(dims.width) {
+                display.set_xy(x, y, &frame.pixels[x + y * self.width]);
+            }
+        }
+    }
+
+    /// The order frames are shown in. Endless unless `mode` is [`PlayMode::Once`].
+    pub fn frame_order(&self, mode: PlayMode) -> Box<dyn Iterator<Item = usize> + Send> {
+        let n = self.frames.len();
+        match mode {
+            PlayMode::Once => Box::new(0..n),
+            PlayMode::Loop => Box::new((0..n).cycle()),
+            // Don't show the first and last frames twice in a row
+            PlayMode::Bounce if n > 2 => Box::new((0..n).chain((1..n - 1).rev()).cycle()),
+            PlayMode::Bounce => Box::new((0..n).cycle()),
+        }
+    }
+
+    /// Show the frames with their delays, fitting them to the display first,
+    /// and hand the display back once done. A still image is shown once,
+    /// whatever the mode, and frames without a delay are shown for as long
+    /// as a GIF frame without one.
+    pub async fn play<T: Display + Send + 'static>(
+        &self,
+        mut display: T,
+        fit: Fit,
+        mode: PlayMode,
+    ) -> Result<T, Error> {
+        let fitted = Arc::new(self.fit(*display.dimensions(), fit));
+        let mode = if fitted.frames.len() == 1 {
+            PlayMode::Once
+        } else {
+            mode
+        };
+
+        for idx in fitted.frame_order(mode) {
+            // Flushing blocks on the hardware, so keep it off the runtime
+            let frame = fitted.clone();
+            display = tokio::task::spawn_blocking(move || {
+                frame.draw(&mut display, idx);
+                display.flush().map(|()| display)
+            })
+            .await
+            .map_err(|e| Error::Io(io::Error::other(e)))??;
+
+            let delay = fitted.frames[idx].delay;
+            if fitted.frames.len() > 1 {
+                tokio::time::sleep(if delay.is_zero() {
+                    DEFAULT_GIF_DELAY
+                } else {
+                    delay
+                })
+                .await;
+            }
+        }
+
+        Ok(display)
+    }
+}
+
+fn over_black(rgb: RGB8, alpha: u8) -> RGB8 {
+    let scale = |v: u8| (v as u16 * alpha as u16 / 255) as u8;
+    RGB8::new(scale(rgb.r), scale(rgb.g), scale(rgb.b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::snapshot::{ImageFormat, SnapshotDisplay};
+
+    const RED: RGB8 = RGB8::new(255, 0, 0);
+    const BLUE: RGB8 = RGB8::new(0, 0, 255);
+    const BLACK: RGB8 = RGB8::new(0, 0, 0);
+
+    fn encode_png(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(rgba).unwrap();
+        writer.finish().unwrap();
+        out
+    }
+
+    fn encode_gif() -> Vec<u8> {
+        let palette = [0, 0, 0, 255, 0, 0, 0, 0, 255];
+        let mut out = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut out, 2, 2, &palette).unwrap();
+
+            // Full red frame, then a blue pixel drawn over its bottom right
+            let mut first = gif::Frame::from_indexed_pixels(2, 2, vec![1; 4], None);
+            first.delay = 5;
+            encoder.write_frame(&first).unwrap();
+
+            let mut second = gif::Frame::from_indexed_pixels(1, 1, vec![2], None);
+            second.left = 1;
+            second.top = 1;
+            second.dispose = DisposalMethod::Background;
+            encoder.write_frame(&second).unwrap();
+
+            let third = gif::Frame::from_indexed_pixels(1, 1, vec![2], None);
+            encoder.write_frame(&third).unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn test_png_alpha_is_drawn_over_black() {
+        let png = encode_png(2, 1, &[255, 0, 0, 255, 0, 0, 255, 128]);
+        let animation = Animation::from_png(png.as_slice()).unwrap();
+
+        assert_eq!((animation.width(), animation.height()), (2, 1));
+        assert_eq!(animation.frames().len(), 1);
+        assert_eq!(animation.frames()[0].pixels, [RED, RGB8::new(0, 0, 128)]);
+    }
+
+    #[test]
+    fn test_gif_frames_are_composited() {
+        let animation = Animation::from_gif(encode_gif().as_slice()).unwrap();
+        let frames = animation.frames();
+
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].pixels, [RED; 4]);
+        assert_eq!(frames[0].delay, Duration::from_millis(50));
+        assert_eq!(frames[1].pixels, [RED, RED, RED, BLUE]);
+        assert_eq!(frames[1].delay, DEFAULT_GIF_DELAY);
+        // The second frame's area was cleared before the third was drawn at 0,0
+        assert_eq!(frames[2].pixels, [BLUE, RED, RED, BLACK]);
+    }
+
+    fn gradient(width: usize, height: usize) -> Animation {
+        Animation {
+            width,
+            height,
+            frames: vec![Frame {
+                pixels: (0..width * height)
+                    .map(|i| RGB8::new(i as u8, 0, 0))
+                    .collect(),
+                delay: Duration::ZERO,
+            }],
+        }
+    }
+
+    fn reds(animation: &Animation) -> Vec<u8> {
+        animation.frames()[0].pixels.iter().map(|px| px.r).collect()
+    }
+
+    #[test]
+    fn test_fit_stretch() {
+        let fitted = gradient(4, 2).fit(
+            Dimensions {
+                width: 2,
+                height: 2,
+            },
+            Fit::Stretch,
+        );
+        assert_eq!(reds(&fitted), [1, 3, 5, 7]);
+    }
+
+    #[test]
+    fn test_fit_cover_crops_centre() {
+        let fitted = gradient(4, 2).fit(
+            Dimensions {
+                width: 2,
+                height: 2,
+            },
+            Fit::Cover,
+        );
+        assert_eq!(reds(&fitted), [1, 2, 5, 6]);
+    }
+
+    #[test]
+    fn test_fit_centre_pads() {
+        let fitted = gradient(1, 1).fit(
+            Dimensions {
+                width: 3,
+                height: 1,
+            },
+            Fit::Centre,
+        );
+        assert_eq!(fitted.frames()[0].pixels, [BLACK, BLACK, BLACK]);
+
+        let fitted = gradient(2, 1).fit(
+            Dimensions {
+                width: 4,
+                height: 1,
+            },
+            Fit::Centre,
+        );
+        assert_eq!(reds(&fitted), [0, 0, 1, 0]);
+    }
+
+    #[test]
+    fn test_frame_order() {
+        let mut animation = gradient(1, 1);
+        animation.frames = vec![animation.frames[0].clone(); 3];
+
+        let order = |mode| animation.frame_order(mode).take(8).collect::<Vec<_>>();
+        assert_eq!(order(PlayMode::Once), [0, 1, 2]);
+        assert_eq!(order(PlayMode::Loop), [0, 1, 2, 0, 1, 2, 0, 1]);
+        assert_eq!(order(PlayMode::Bounce), [0, 1, 2, 1, 0, 1, 2, 1]);
+    }
+
+    #[test]
+    fn test_from_frames_checks_sizes() {
+        let frame = |n| Frame {
+            pixels: vec![RED; n],
+            delay: Duration::from_millis(20),
+        };
+
+        let animation = Animation::from_frames(2, 1, vec![frame(2), frame(2)]).unwrap();
+        assert_eq!(animation.frames().len(), 2);
+        assert!(Animation::from_frames(2, 1, vec![frame(2), frame(3)]).is_err());
+        assert!(Animation::from_frames(2, 1, vec![]).is_err());
+    }
+
+    #[test]
+    fn test_open_sequence() {
+        let dir = std::env::temp_dir().join(format!("unicorn-sequence-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, bytes: Vec<u8>| {
+            let path = dir.join(name);
+            fs::write(&path, bytes).unwrap();
+            path
+        };
+        let red = write("red.png", encode_png(2, 2, &[255, 0, 0, 255].repeat(4)));
+        let gif = write("anim.gif", encode_gif());
+        let small = write("small.png", encode_png(1, 1, &[0, 0, 255, 255]));
+
+        let delay = Duration::from_millis(40);
+        let animation = Animation::open_sequence([&red, &gif, &red], delay).unwrap();
+        let frames = animation.frames();
+        assert_eq!(frames.len(), 5);
+        assert_eq!(frames[0].pixels, [RED; 4]);
+        assert_eq!(frames[0].delay, delay);
+        assert_eq!(frames[1].delay, Duration::from_millis(50));
+        assert_eq!(frames[4].delay, delay);
+
+        assert!(Animation::open_sequence([&red, &small], delay).is_err());
+        assert!(Animation::open_sequence(Vec::<&Path>::new(), delay).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_play_once() {
+        let animation = Animation::from_gif(encode_gif().as_slice()).unwrap();
+        let display = SnapshotDisplay::new(Dimensions::UNICORN_MINI, 1, ImageFormat::Ppm);
+
+        let display = animation
+            .play(display, Fit::Stretch, PlayMode::Once)
+            .await
+            .unwrap();
+
+        assert_eq!(display.frames_flushed(), 3);
+        assert_eq!(display.pixel(16, 6), BLACK);
+        assert_eq!(display.pixel(0, 0), BLUE);
+    }
+
+    #[tokio::test]
+    async fn test_play_still_image_once() {
+        let png = encode_png(1, 1, &[255, 0, 0, 255]);
+        let animation = Animation::from_png(png.as_slice()).unwrap();
+        let display = SnapshotDisplay::new(Dimensions::UNICORN_MINI, 1, ImageFormat::Ppm);
+
+        let display = animation
+            .play(display, Fit::Stretch, PlayMode::Loop)
+            .await
+            .unwrap();
+
+        assert_eq!(display.frames_flushed(), 1);
+        assert_eq!(display.pixel(16, 6), RED);
+    }
+}
@@ -0,0 +1,508 @@
+use std::{
+    collections::HashMap,
+    os::unix::io::AsRawFd,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use inotify::{EventMask, Inotify, WatchMask};
+use libc::input_event;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+
+use super::{
+    devices::{self, InputDeviceInfo},
+    event::{KeyDecoder, KeyEvent},
+    keymap::Keymap,
+    wait_readable, InputDevice,
+};
+use crate::error::Error;
+
+const INPUT_DIR: &str = "/dev/input";
+/// How often the threads check if they've been shut down.
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+const EVENT_CAPACITY: usize = 64;
+
+/// Picks out input devices by what `/proc/bus/input/devices` says about them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeviceMatch {
+    /// Name containing this, e.g. `"Logitech"`.
+    Name(String),
+    /// Physical path containing this, e.g. `"usb-3f980000.usb-1.3"`.
+    Phys(String),
+    Id {
+        vendor: u16,
+        product: u16,
+    },
+}
+
+impl DeviceMatch {
+    pub(crate) fn matches(&self, device: &InputDeviceInfo) -> bool {
+        match self {
+            DeviceMatch::Name(name) => device.name.contains(name.as_str()),
+            DeviceMatch::Phys(phys) => device.phys.contains(phys.as_str()),
+            DeviceMatch::Id { vendor, product } => {
+                device.vendor == *vendor && device.product == *product
+            }
+        }
+    }
+}
+
+/// Which keyboards to capture and how.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CaptureOptions {
+    /// Grab the devices with `EVIOCGRAB`, so nothing else (like the console)
+    /// sees their input. Otherwise listen alongside everyone else.
+    pub exclusive: bool,
+    /// Capture only devices matching one of these, whether or not they look
+    /// like keyboards. When empty, every keyboard is captured.
+    pub include: Vec<DeviceMatch>,
+    /// Never capture devices matching any of these.
+    pub exclude: Vec<DeviceMatch>,
+    pub keymap: Option<Keymap>,
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        CaptureOptions {
+            exclusive: true,
+            include: vec![],
+            exclude: vec![],
+            keymap: None,
+        }
+    }
+}
+
+impl CaptureOptions {
+    pub(crate) fn selects(&self, device: &InputDeviceInfo) -> bool {
+        if self.exclude.iter().any(|m| m.matches(device)) {
+            return false;
+        }
+
+        if self.include.is_empty() {
+            device.is_keyboard()
+        } else {
+            self.include.iter().any(|m| m.matches(device))
+        }
+    }
+}
+
+/// What to capture, and how to turn each device's raw events into the
+/// events of a [`Capture`].
+pub(crate) trait Source: Send + 'static {
+    type Event: Send + 'static;
+    type Decoder: Send + 'static;
+
+    /// Thread name, e.g. `unicorn-keyboards`.
+    const NAME: &'static str;
+
+    fn selects(&self, device: &InputDeviceInfo) -> bool;
+    fn exclusive(&self) -> bool;
+    fn decoder(&self, info: &InputDeviceInfo, device: &InputDevice) -> Self::Decoder;
+    fn decode(decoder: &mut Self::Decoder, event: &input_event) -> Option<Self::Event>;
+    fn added(info: InputDeviceInfo) -> Self::Event;
+    fn removed(info: InputDeviceInfo) -> Self::Event;
+}
+
+impl Source for CaptureOptions {
+    type Event = CaptureEvent;
+    type Decoder = KeyDecoder;
+
+    const NAME: &'static str = "unicorn-keyboards";
+
+    fn selects(&self, device: &InputDeviceInfo) -> bool {
+        self.selects(device)
+    }
+
+    fn exclusive(&self) -> bool {
+        self.exclusive
+    }
+
+    fn decoder(&self, info: &InputDeviceInfo, device: &InputDevice) -> KeyDecoder {
+        let name = device.name().unwrap_or_else(|_| info.name.clone());
+        KeyDecoder::new(name, self.keymap)
+    }
+
+    fn decode(decoder: &mut KeyDecoder, event: &input_event) -> Option<CaptureEvent> {
+        decoder.decode(event).map(CaptureEvent::Key)
+    }
+
+    fn added(info: InputDeviceInfo) -> CaptureEvent {
+        CaptureEvent::DeviceAdded(info)
+    }
+
+    fn removed(info: InputDeviceInfo) -> CaptureEvent {
+        CaptureEvent::DeviceRemoved(info)
+    }
+}
+
+/// Something that happened to the captured keyboards.
+#[derive(Clone, Debug)]
+pub enum CaptureEvent {
+    Key(KeyEvent),
+    /// A device has been captured, either at the start or when plugged in.
+    DeviceAdded(InputDeviceInfo),
+    /// A captured device has been unplugged.
+    DeviceRemoved(InputDeviceInfo),
+}
+
+/// Stops a capture from anywhere, releasing its grabs.
+#[derive(Clone, Debug)]
+pub struct ShutdownHandle {
+    stop: Arc<AtomicBool>,
+}
+
+impl ShutdownHandle {
+    pub fn shutdown(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+}
+
+/// Handle on a thread watching `/dev/input` and one per captured device,
+/// which are stopped when it's dropped or shut down.
+pub struct Capture<E> {
+    events: Receiver<E>,
+    shutdown: ShutdownHandle,
+    watcher: Option<JoinHandle<()>>,
+}
+
+/// Captured keyboards.
+pub type Keyboards = Capture<CaptureEvent>;
+
+impl<E: Send + 'static> Capture<E> {
+    pub(crate) fn start<S: Source<Event = E>>(source: S) -> Result<Self, Error> {
+        let mut inotify = Inotify::init().map_err(Error::Io)?;
+        // Devices may only become readable once udev has set their permissions
+        inotify
+            .add_watch(
+                INPUT_DIR,
+                WatchMask::CREATE | WatchMask::ATTRIB | WatchMask::DELETE,
+            )
+            .map_err(Error::Io)?;
+
+        let shutdown = ShutdownHandle {
+            stop: Arc::new(AtomicBool::new(false)),
+        };
+        let (tx, events) = mpsc::channel(EVENT_CAPACITY);
+        let mut watcher = Watcher {
+            source,
+            stop: shutdown.stop.clone(),
+            tx,
+            attached: Arc::new(Attached::default()),
+            readers: vec![],
+        };
+
+        let watcher = thread::Builder::new()
+            .name(S::NAME.to_string())
+            .spawn(move || watcher.run(inotify))
+            .map_err(Error::Io)?;
+
+        Ok(Capture {
+            events,
+            shutdown,
+            watcher: Some(watcher),
+        })
+    }
+
+    /// Wait for the next event, or `None` once shut down.
+    pub async fn next(&mut self) -> Option<E> {
+        self.events.recv().await
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Stop capturing, returning once every device has been released.
+    pub async fn shutdown(mut self) {
+        self.shutdown.shutdown();
+        // Unblock the threads if they're waiting to send
+        self.events.close();
+        if let Some(watcher) = self.watcher.take() {
+            tokio::task::spawn_blocking(move || watcher.join())
+                .await
+                .ok();
+        }
+    }
+}
+
+impl<E> Drop for Capture<E> {
+    fn drop(&mut self) {
+        self.shutdown.shutdown();
+    }
+}
+
+/// The devices currently being read, each with a flag to stop its reader.
+#[derive(Default)]
+struct Attached {
+    devices: Mutex<HashMap<String, (Arc<AtomicBool>, InputDeviceInfo)>>,
+}
+
+impl Attached {
+    /// Record that `node` is being read, unless it already is.
+    fn insert(&self, node: &str, info: &InputDeviceInfo) -> Option<Arc<AtomicBool>> {
+        let mut devices = self.devices.lock().unwrap();
+        if devices.contains_key(node) {
+            return None;
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        devices.insert(node.to_owned(), (stop.clone(), info.clone()));
+        Some(stop)
+    }
+
+    fn contains(&self, node: &str) -> bool {
+        self.devices.lock().unwrap().contains_key(node)
+    }
+
+    /// Stop reading `node`, if it's still read by the reader with `stop`
+    /// (or any reader, if `None`), returning what it was.
+    fn remove(&self, node: &str, stop: Option<&Arc<AtomicBool>>) -> Option<InputDeviceInfo> {
+        let mut devices = self.devices.lock().unwrap();
+        let (current, _) = devices.get(node)?;
+        if stop.is_some_and(|stop| !Arc::ptr_eq(stop, current)) {
+            return None;
+        }
+        let (current, info) = devices.remove(node)?;
+        current.store(true, Ordering::Relaxed);
+        Some(info)
+    }
+}
+
+struct Watcher<S: Source> {
+    source: S,
+    stop: Arc<AtomicBool>,
+    tx: Sender<S::Event>,
+    attached: Arc<Attached>,
+    readers: Vec<JoinHandle<()>>,
+}
+
+impl<S: Source> Watcher<S> {
+    fn run(&mut self, mut inotify: Inotify) {
+        for info in self.selected_devices() {
+            self.attach(info);
+        }
+
+        let mut buffer = [0u8; 4096];
+        while !self.stop.load(Ordering::Relaxed) {
+            match wait_readable(inotify.as_raw_fd(), STOP_CHECK_INTERVAL) {
+                Ok(false) => continue,
+                Ok(true) => {}
+                Err(e) => {
+                    log::error!("Stopped watching for keyboards: {}", e);
+                    break;
+                }
+            }
+            let events = match inotify.read_events(&mut buffer) {
+                Ok(events) => events,
+                Err(e) => {
+                    log::error!("Stopped watching for keyboards: {}", e);
+                    break;
+                }
+            };
+
+            for event in events {
+                let Some(node) = event.name.and_then(|name| name.to_str()) else {
+                    continue;
+                };
+                if event.mask.contains(EventMask::ISDIR) {
+                    continue;
+                }
+
+                if event.mask.contains(EventMask::DELETE) {
+                    if let Some(info) = self.attached.remove(node, None) {
+                        self.send(S::removed(info));
+                    }
+                } else if !self.attached.contains(node) {
+                    let info = self
+                        .selected_devices()
+                        .into_iter()
+                        .find(|info| info.event_node() == Some(node));
+                    if let Some(info) = info {
+                        self.attach(info);
+                    }
+                }
+            }
+            self.readers.retain(|reader| !reader.is_finished());
+        }
+
+        // Nobody's listening, or we've been told to stop
+        self.stop.store(true, Ordering::Relaxed);
+        for reader in self.readers.drain(..) {
+            reader.join().ok();
+        }
+    }
+
+    fn selected_devices(&self) -> Vec<InputDeviceInfo> {
+        devices::read_devices(devices::PROC_DEVICES)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|info| info.event_node().is_some() && self.source.selects(info))
+            .collect()
+    }
+
+    fn send(&self, event: S::Event) {
+        if self.tx.blocking_send(event).is_err() {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn attach(&mut self, info: InputDeviceInfo) {
+        let Some(node) = info.event_node().map(str::to_owned) else {
+            return;
+        };
+        if self.attached.contains(&node) {
+            return;
+        }
+
+        let mut device = match InputDevice::open(&format!("{}/{}", INPUT_DIR, node)) {
+            Ok(device) => device,
+            // Perhaps its permissions aren't set yet, so wait for them to change
+            Err(e) => return log::debug!("Couldn't open {}: {}", node, e),
+        };
+        if self.source.exclusive() {
+            if let Err(e) = device.grab() {
+                return log::warn!("Couldn't grab {}: {}", node, e);
+            }
+        }
+        let Some(device_stop) = self.attached.insert(&node, &info) else {
+            return;
+        };
+        log::info!("Capturing {} ({})", node, info.name);
+        self.send(S::added(info.clone()));
+
+        let reader = Reader::<S> {
+            node: node.clone(),
+            decoder: self.source.decoder(&info, &device),
+            device,
+            stop: self.stop.clone(),
+            device_stop,
+            tx: self.tx.clone(),
+            attached: self.attached.clone(),
+        };
+        match thread::Builder::new()
+            .name(format!("unicorn-{}", node))
+            .spawn(move || reader.run())
+        {
+            Ok(handle) => self.readers.push(handle),
+            Err(e) => {
+                log::error!("Couldn't read {}: {}", node, e);
+                self.attached.remove(&node, None);
+            }
+        }
+    }
+}
+
+struct Reader<S: Source> {
+    node: String,
+    device: InputDevice,
+    decoder: S::Decoder,
+    stop: Arc<AtomicBool>,
+    /// Set when this device alone should stop being read.
+    device_stop: Arc<AtomicBool>,
+    tx: Sender<S::Event>,
+    attached: Arc<Attached>,
+}
+
+impl<S: Source> Reader<S> {
+    fn run(mut self) {
+        while !self.stop.load(Ordering::Relaxed) && !self.device_stop.load(Ordering::Relaxed) {
+            match self.device.next_event(STOP_CHECK_INTERVAL) {
+                Ok(Some(event)) => {
+                    if let Some(event) = S::decode(&mut self.decoder, &event) {
+                        if self.tx.blocking_send(event).is_err() {
+                            break;
+                        }
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    log::info!("Lost device {}: {}", self.node, e);
+                    if let Some(info) = self.attached.remove(&self.node, Some(&self.device_stop)) {
+                        self.tx.blocking_send(S::removed(info)).ok();
+                    }
+                    return;
+                }
+            }
+        }
+        self.attached.remove(&self.node, Some(&self.device_stop));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyboard::devices::{parse_devices, WORD_BITS};
+
+    fn device(name: &str, phys: &str, keyboard: bool) -> InputDeviceInfo {
+        let ev = if keyboard { "120013" } else { "17" };
+        let block = format!(
+            "I: Bus=0003 Vendor=046d Product=c52b Version=0111\n\
+             N: Name=\"{}\"\n\
+             P: Phys={}\n\
+             H: Handlers=event0\n\
+             B: EV={}\n\
+             B: KEY=fffffffffffffffe\n",
+            name, phys, ev
+        );
+        parse_devices(block.as_bytes(), WORD_BITS)
+            .unwrap()
+            .remove(0)
+    }
+
+    #[test]
+    fn test_all_keyboards_by_default() {
+        let options = CaptureOptions::default();
+
+        assert!(options.selects(&device("Keyboard", "usb-1", true)));
+        assert!(!options.selects(&device("Mouse", "usb-2", false)));
+    }
+
+    #[test]
+    fn test_include_and_exclude() {
+        let options = CaptureOptions {
+            include: vec![
+                DeviceMatch::Name("Remote".to_string()),
+                DeviceMatch::Id {
+                    vendor: 0x046d,
+                    product: 0xc52b,
+                },
+            ],
+            exclude: vec![DeviceMatch::Phys("usb-3".to_string())],
+            ..Default::default()
+        };
+
+        // Included devices needn't look like keyboards
+        assert!(options.selects(&device("IR Remote", "ir-1", false)));
+        assert!(options.selects(&device("Logitech Receiver", "usb-1", true)));
+        assert!(!options.selects(&device("Logitech Receiver", "usb-3", true)));
+
+        let mut other = device("Other Keyboard", "usb-2", true);
+        other.vendor = 0x1234;
+        assert!(!options.selects(&other));
+    }
+
+    #[test]
+    fn test_attached_once() {
+        let attached = Attached::default();
+        let info = device("Keyboard", "usb-1", true);
+
+        let first = attached.insert("event0", &info).unwrap();
+        assert!(attached.insert("event0", &info).is_none());
+
+        // A reader which was replaced doesn't remove its replacement
+        assert_eq!(attached.remove("event0", None), Some(info.clone()));
+        assert!(first.load(Ordering::Relaxed));
+        let second = attached.insert("event0", &info).unwrap();
+        assert_eq!(attached.remove("event0", Some(&first)), None);
+        assert!(attached.contains("event0"));
+        assert_eq!(attached.remove("event0", Some(&second)), Some(info));
+        assert!(!attached.contains("event0"));
+    }
+}
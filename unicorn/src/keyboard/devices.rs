@@ -0,0 +1,290 @@
+// Parsing of the input device list the kernel publishes at
+// /proc/bus/input/devices, one blank-line-separated block per device:
+//
+// I: Bus=0003 Vendor=04d9 Product=0007 Version=0111
+// N: Name="Pi 400 Keyboard"
+// P: Phys=usb-0000:01:00.0-1.4/input0
+// S: Sysfs=/devices/platform/scb/.../input/input3
+// U: Uniq=
+// H: Handlers=sysrq kbd leds event3
+// B: PROP=0
+// B: EV=120013
+// B: KEY=1000000000007 ff9f207ac14057ff febeffdfffefffff fffffffffffffffe
+// ...
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    mem,
+    path::Path,
+};
+
+pub const PROC_DEVICES: &str = "/proc/bus/input/devices";
+
+const EV_KEY: usize = 0x01;
+const EV_REP: usize = 0x14;
+const KEY_A: usize = 30;
+const KEY_Z: usize = 44;
+/// From `BTN_JOYSTICK`, through the `BTN_GAMEPAD` buttons, to `BTN_THUMBR`.
+const JOYSTICK_BUTTONS: std::ops::RangeInclusive<usize> = 0x120..=0x13e;
+
+/// The kernel prints bitmaps in words the size of its `long`, so this is
+/// the word size of lists read from the running kernel.
+pub const WORD_BITS: usize = mem::size_of::<libc::c_long>() * 8;
+
+/// A capability bitmap from a `B:` line, e.g. which key codes a device has.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bitmap {
+    /// Least significant word first.
+    words: Vec<u64>,
+    word_bits: usize,
+}
+
+impl Bitmap {
+    fn parse(s: &str, word_bits: usize) -> Option<Self> {
+        let words = s
+            .split_whitespace()
+            .rev()
+            .map(|word| u64::from_str_radix(word, 16).ok())
+            .collect::<Option<Vec<_>>>()?;
+        Some(Bitmap { words, word_bits })
+    }
+
+    pub fn contains(&self, bit: usize) -> bool {
+        self.words
+            .get(bit / self.word_bits)
+            .is_some_and(|word| word >> (bit % self.word_bits) & 1 == 1)
+    }
+
+    /// Every set bit, lowest first.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.words.len() * self.word_bits).filter(|bit| self.contains(*bit))
+    }
+}
+
+/// One device from `/proc/bus/input/devices`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InputDeviceInfo {
+    pub bus: u16,
+    pub vendor: u16,
+    pub product: u16,
+    pub version: u16,
+    pub name: String,
+    pub phys: String,
+    pub sysfs: String,
+    pub uniq: String,
+    /// Handlers bound to the device, e.g. `kbd`, `event3`, `js0`.
+    pub handlers: Vec<String>,
+    /// Bitmaps keyed by type, e.g. `EV`, `KEY`, `ABS`.
+    pub capabilities: BTreeMap<String, Bitmap>,
+}
+
+impl InputDeviceInfo {
+    /// File name of the device's node under `/dev/input`, e.g. `event3`.
+    pub fn event_node(&self) -> Option<&str> {
+        self.handlers
+            .iter()
+            .map(String::as_str)
+            .find(|h| h.starts_with("event"))
+    }
+
+    pub fn capability(&self, kind: &str) -> Option<&Bitmap> {
+        self.capabilities.get(kind)
+    }
+
+    pub fn has_event_type(&self, ev: usize) -> bool {
+        self.capability("EV").is_some_and(|b| b.contains(ev))
+    }
+
+    pub fn has_key(&self, code: usize) -> bool {
+        self.capability("KEY").is_some_and(|b| b.contains(code))
+    }
+
+    /// Has autorepeating keys including the letters, so not a mouse, remote
+    /// control or the power button.
+    pub fn is_keyboard(&self) -> bool {
+        self.has_event_type(EV_KEY)
+            && self.has_event_type(EV_REP)
+            && (KEY_A..=KEY_Z).any(|code| self.has_key(code))
+    }
+
+    /// Has joystick or gamepad buttons. Most have axes too, which arcade
+    /// encoders also use for their sticks.
+    pub fn is_gamepad(&self) -> bool {
+        self.has_event_type(EV_KEY) && JOYSTICK_BUTTONS.into_iter().any(|code| self.has_key(code))
+    }
+
+    fn parse_line(&mut self, line: &str, word_bits: usize) {
+        let Some((kind, value)) = line.split_once(": ") else {
+            return;
+        };
+
+        match kind {
+            "I" => {
+                for (field, value) in value.split_whitespace().filter_map(|f| f.split_once('=')) {
+                    let value = u16::from_str_radix(value, 16).unwrap_or_default();
+                    match field {
+                        "Bus" => self.bus = value,
+                        "Vendor" => self.vendor = value,
+                        "Product" => self.product = value,
+                        "Version" => self.version = value,
+                        _ => {}
+                    }
+                }
+            }
+            "N" => {
+                let name = value.strip_prefix("Name=").unwrap_or(value);
+                self.name = name.trim_matches('"').to_owned();
+            }
+            "P" => self.phys = value.strip_prefix("Phys=").unwrap_or(value).to_owned(),
+            "S" => self.sysfs = value.strip_prefix("Sysfs=").unwrap_or(value).to_owned(),
+            "U" => self.uniq = value.strip_prefix("Uniq=").unwrap_or(value).to_owned(),
+            "H" => {
+                let handlers = value.strip_prefix("Handlers=").unwrap_or(value);
+                self.handlers = handlers.split_whitespace().map(str::to_owned).collect();
+            }
+            "B" => {
+                if let Some((kind, bitmap)) = value.split_once('=') {
+                    if let Some(bitmap) = Bitmap::parse(bitmap, word_bits) {
+                        self.capabilities.insert(kind.to_owned(), bitmap);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Parse the device list in the format of `/proc/bus/input/devices`, from a
+/// kernel whose `long` is `word_bits` wide, normally [`WORD_BITS`]. Lines
+/// that aren't understood are skipped.
+pub fn parse_devices(reader: impl BufRead, word_bits: usize) -> io::Result<Vec<InputDeviceInfo>> {
+    let mut devices = Vec::new();
+    let mut device = InputDeviceInfo::default();
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            if device != InputDeviceInfo::default() {
+                devices.push(mem::take(&mut device));
+            }
+        } else {
+            device.parse_line(&line, word_bits);
+        }
+    }
+    if device != InputDeviceInfo::default() {
+        devices.push(device);
+    }
+
+    Ok(devices)
+}
+
+/// Read the running kernel's device list from `path`, normally [`PROC_DEVICES`].
+pub fn read_devices(path: impl AsRef<Path>) -> io::Result<Vec<InputDeviceInfo>> {
+    parse_devices(BufReader::new(File::open(path)?), WORD_BITS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The fixtures were all captured on 64 bit kernels.
+    fn fixture(name: &str) -> Vec<InputDeviceInfo> {
+        let path = format!(
+            "{}/tests/fixtures/input-devices/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        );
+        parse_devices(BufReader::new(File::open(path).unwrap()), 64).unwrap()
+    }
+
+    fn keyboards(devices: &[InputDeviceInfo]) -> Vec<&str> {
+        devices
+            .iter()
+            .filter(|d| d.is_keyboard())
+            .map(|d| d.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_bitmap() {
+        let bitmap = Bitmap::parse("3 0 8001", 64).unwrap();
+
+        assert!(bitmap.contains(0));
+        assert!(bitmap.contains(15));
+        assert!(!bitmap.contains(1));
+        assert!(bitmap.contains(129));
+        assert!(!bitmap.contains(640));
+        assert_eq!(bitmap.iter().collect::<Vec<_>>(), vec![0, 15, 128, 129]);
+        assert!(Bitmap::parse("12 xyz", 64).is_none());
+
+        // The same words from a 32 bit kernel
+        let bitmap = Bitmap::parse("3 0 8001", 32).unwrap();
+        assert_eq!(bitmap.iter().collect::<Vec<_>>(), vec![0, 15, 64, 65]);
+    }
+
+    #[test]
+    fn test_pi_400() {
+        let devices = fixture("pi400.txt");
+
+        assert_eq!(devices.len(), 4);
+        let kbd = &devices[2];
+        assert_eq!(
+            (kbd.bus, kbd.vendor, kbd.product, kbd.version),
+            (0x0003, 0x04d9, 0x0007, 0x0111)
+        );
+        assert_eq!(kbd.name, "Pi 400 Keyboard");
+        assert_eq!(kbd.phys, "usb-0000:01:00.0-1.4/input0");
+        assert!(kbd.sysfs.ends_with("/input/input2"));
+        assert_eq!(kbd.handlers, vec!["sysrq", "kbd", "leds", "event2"]);
+        assert_eq!(kbd.event_node(), Some("event2"));
+        assert!(kbd.capability("LED").is_some_and(|b| b.contains(0)));
+
+        // The HDMI CEC inputs and the consumer controls look like keyboards
+        // by their event types alone
+        assert_eq!(keyboards(&devices), vec!["Pi 400 Keyboard"]);
+    }
+
+    #[test]
+    fn test_usb_keyboard_and_mouse() {
+        let devices = fixture("pi4-usb-keyboard.txt");
+
+        assert_eq!(devices.len(), 6);
+        assert_eq!(keyboards(&devices), vec!["Logitech USB Receiver"]);
+        assert!(!devices.iter().any(|d| d.is_gamepad()));
+        let mouse = devices
+            .iter()
+            .find(|d| d.name == "Logitech USB Receiver Mouse")
+            .unwrap();
+        assert_eq!(mouse.handlers, vec!["mouse0", "event4"]);
+        assert!(mouse.has_event_type(0x02));
+    }
+
+    #[test]
+    fn test_gamepad() {
+        let devices = fixture("gamepad.txt");
+
+        let pad = &devices[0];
+        assert_eq!((pad.vendor, pad.product), (0x045e, 0x02ea));
+        assert_eq!(pad.handlers, vec!["event5", "js0"]);
+        // BTN_SOUTH and BTN_THUMBR
+        assert!(pad.has_key(0x130));
+        assert!(pad.has_key(0x13e));
+        assert!(pad.capability("ABS").is_some_and(|b| b.contains(0x00)));
+        assert!(keyboards(&devices).is_empty());
+        assert!(devices.iter().all(|d| d.is_gamepad()));
+    }
+
+    #[test]
+    fn test_ir_remote() {
+        let devices = fixture("ir-remote.txt");
+
+        let remote = &devices[0];
+        assert_eq!(remote.name, "gpio_ir_recv");
+        assert_eq!(remote.uniq, "");
+        assert_eq!(remote.event_node(), Some("event0"));
+        assert!(remote.has_key(103));
+        assert!(keyboards(&devices).is_empty());
+    }
+}
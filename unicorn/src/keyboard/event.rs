@@ -0,0 +1,174 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use libc::input_event;
+
+use super::{keymap::Keymap, keys::Key};
+
+const EV_KEY: u16 = 0x01;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KeyState {
+    Released,
+    Pressed,
+    /// Held down long enough for the keyboard to repeat it.
+    Repeat,
+}
+
+/// Modifiers in effect, on either side of the keyboard.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub caps_lock: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: Key,
+    pub state: KeyState,
+    /// Modifiers after this event.
+    pub modifiers: Modifiers,
+    /// Character typed, if a keymap was given and this is a press or repeat.
+    pub text: Option<char>,
+    /// Name the keyboard reports for itself.
+    pub device: String,
+    pub time: SystemTime,
+}
+
+/// Turns one keyboard's raw events into key events, tracking its modifiers.
+pub(crate) struct KeyDecoder {
+    device: String,
+    keymap: Option<Keymap>,
+    left: Modifiers,
+    right: Modifiers,
+    caps_lock: bool,
+}
+
+impl KeyDecoder {
+    pub fn new(device: String, keymap: Option<Keymap>) -> Self {
+        KeyDecoder {
+            device,
+            keymap,
+            left: Modifiers::default(),
+            right: Modifiers::default(),
+            caps_lock: false,
+        }
+    }
+
+    /// Decode `event`, or `None` if it isn't a key event.
+    pub fn decode(&mut self, event: &input_event) -> Option<KeyEvent> {
+        if event.type_ != EV_KEY {
+            return None;
+        }
+        let key = Key::from_code(event.code);
+        let state = match event.value {
+            0 => KeyState::Released,
+            1 => KeyState::Pressed,
+            2 => KeyState::Repeat,
+            _ => return None,
+        };
+
+        let down = state != KeyState::Released;
+        match key {
+            Key::LeftShift => self.left.shift = down,
+            Key::RightShift => self.right.shift = down,
+            Key::LeftCtrl => self.left.ctrl = down,
+            Key::RightCtrl => self.right.ctrl = down,
+            Key::LeftAlt => self.left.alt = down,
+            Key::RightAlt => self.right.alt = down,
+            Key::CapsLock if state == KeyState::Pressed => self.caps_lock = !self.caps_lock,
+            _ => {}
+        }
+
+        let modifiers = Modifiers {
+            shift: self.left.shift || self.right.shift,
+            ctrl: self.left.ctrl || self.right.ctrl,
+            alt: self.left.alt || self.right.alt,
+            caps_lock: self.caps_lock,
+        };
+        let text = self
+            .keymap
+            .filter(|_| down)
+            .and_then(|keymap| keymap.text(key, &modifiers));
+        let time = UNIX_EPOCH
+            + Duration::from_secs(event.time.tv_sec as u64)
+            + Duration::from_micros(event.time.tv_usec as u64);
+
+        Some(KeyEvent {
+            key,
+            state,
+            modifiers,
+            text,
+            device: self.device.clone(),
+            time,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(type_: u16, code: Key, value: i32) -> input_event {
+        input_event {
+            time: libc::timeval {
+                tv_sec: 10,
+                tv_usec: 500,
+            },
+            type_,
+            code: code.code(),
+            value,
+        }
+    }
+
+    fn typed(decoder: &mut KeyDecoder, key: Key, value: i32) -> Option<char> {
+        decoder.decode(&raw(EV_KEY, key, value)).unwrap().text
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut decoder = KeyDecoder::new("Keyboard".to_string(), None);
+
+        let event = decoder.decode(&raw(EV_KEY, Key::Enter, 2)).unwrap();
+        assert_eq!(event.key, Key::Enter);
+        assert_eq!(event.state, KeyState::Repeat);
+        assert_eq!(event.text, None);
+        assert_eq!(event.device, "Keyboard");
+        assert_eq!(
+            event.time,
+            UNIX_EPOCH + Duration::from_secs(10) + Duration::from_micros(500)
+        );
+
+        // Sync and scan code events
+        assert_eq!(decoder.decode(&raw(0x00, Key::Other(0), 0)), None);
+        assert_eq!(decoder.decode(&raw(0x04, Key::Other(4), 30)), None);
+    }
+
+    #[test]
+    fn test_tracks_modifiers() {
+        let mut decoder = KeyDecoder::new("Keyboard".to_string(), Some(Keymap::Us));
+
+        assert_eq!(typed(&mut decoder, Key::A, 1), Some('a'));
+        decoder.decode(&raw(EV_KEY, Key::LeftShift, 1));
+        decoder.decode(&raw(EV_KEY, Key::RightShift, 1));
+        decoder.decode(&raw(EV_KEY, Key::LeftShift, 0));
+        assert_eq!(typed(&mut decoder, Key::A, 1), Some('A'));
+        assert_eq!(typed(&mut decoder, Key::A, 2), Some('A'));
+        assert_eq!(typed(&mut decoder, Key::A, 0), None);
+
+        let event = decoder.decode(&raw(EV_KEY, Key::RightShift, 0)).unwrap();
+        assert_eq!(event.modifiers, Modifiers::default());
+
+        // Caps lock toggles on each press, not on repeats
+        decoder.decode(&raw(EV_KEY, Key::CapsLock, 1));
+        decoder.decode(&raw(EV_KEY, Key::CapsLock, 2));
+        decoder.decode(&raw(EV_KEY, Key::CapsLock, 0));
+        assert_eq!(typed(&mut decoder, Key::B, 1), Some('B'));
+
+        decoder.decode(&raw(EV_KEY, Key::RightCtrl, 1));
+        let event = decoder.decode(&raw(EV_KEY, Key::C, 1)).unwrap();
+        assert!(event.modifiers.ctrl && event.modifiers.caps_lock);
+        assert_eq!(event.text, None);
+    }
+}
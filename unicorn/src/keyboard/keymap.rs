@@ -0,0 +1,182 @@
+use super::{event::Modifiers, keys::Key};
+
+/// Layout used to turn keys into the text they type.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Keymap {
+    Us,
+    Uk,
+}
+
+impl Keymap {
+    /// The character typed by `key`, if any. Nothing is typed while Ctrl or
+    /// Alt are held, as those are shortcuts.
+    pub fn text(&self, key: Key, modifiers: &Modifiers) -> Option<char> {
+        if modifiers.ctrl || modifiers.alt {
+            return None;
+        }
+
+        if let Some(letter) = letter(key) {
+            return Some(if modifiers.shift != modifiers.caps_lock {
+                letter.to_ascii_uppercase()
+            } else {
+                letter
+            });
+        }
+
+        let (plain, shifted) = match (self, key) {
+            (Keymap::Uk, Key::Digit2) => ('2', '"'),
+            (Keymap::Uk, Key::Digit3) => ('3', '£'),
+            (Keymap::Uk, Key::Apostrophe) => ('\'', '@'),
+            (Keymap::Uk, Key::Grave) => ('`', '¬'),
+            (Keymap::Uk, Key::Backslash) => ('#', '~'),
+            (_, key) => us_symbol(key)?,
+        };
+        Some(if modifiers.shift { shifted } else { plain })
+    }
+}
+
+fn letter(key: Key) -> Option<char> {
+    let letter = match key {
+        Key::A => 'a',
+        Key::B => 'b',
+        Key::C => 'c',
+        Key::D => 'd',
+        Key::E => 'e',
+        Key::F => 'f',
+        Key::G => 'g',
+        Key::H => 'h',
+        Key::I => 'i',
+        Key::J => 'j',
+        Key::K => 'k',
+        Key::L => 'l',
+        Key::M => 'm',
+        Key::N => 'n',
+        Key::O => 'o',
+        Key::P => 'p',
+        Key::Q => 'q',
+        Key::R => 'r',
+        Key::S => 's',
+        Key::T => 't',
+        Key::U => 'u',
+        Key::V => 'v',
+        Key::W => 'w',
+        Key::X => 'x',
+        Key::Y => 'y',
+        Key::Z => 'z',
+        _ => return None,
+    };
+    Some(letter)
+}
+
+/// Unshifted and shifted characters of every other key which types something.
+fn us_symbol(key: Key) -> Option<(char, char)> {
+    let pair = match key {
+        Key::Digit1 => ('1', '!'),
+        Key::Digit2 => ('2', '@'),
+        Key::Digit3 => ('3', '#'),
+        Key::Digit4 => ('4', '$'),
+        Key::Digit5 => ('5', '%'),
+        Key::Digit6 => ('6', '^'),
+        Key::Digit7 => ('7', '&'),
+        Key::Digit8 => ('8', '*'),
+        Key::Digit9 => ('9', '('),
+        Key::Digit0 => ('0', ')'),
+        Key::Minus => ('-', '_'),
+        Key::Equal => ('=', '+'),
+        Key::LeftBrace => ('[', '{'),
+        Key::RightBrace => (']', '}'),
+        Key::Semicolon => (';', ':'),
+        Key::Apostrophe => ('\'', '"'),
+        Key::Grave => ('`', '~'),
+        Key::Backslash | Key::NonUsBackslash => ('\\', '|'),
+        Key::Comma => (',', '<'),
+        Key::Dot => ('.', '>'),
+        Key::Slash => ('/', '?'),
+        Key::Space => (' ', ' '),
+        Key::Tab => ('\t', '\t'),
+        Key::Enter | Key::KpEnter => ('\n', '\n'),
+        Key::Kp0 => ('0', '0'),
+        Key::Kp1 => ('1', '1'),
+        Key::Kp2 => ('2', '2'),
+        Key::Kp3 => ('3', '3'),
+        Key::Kp4 => ('4', '4'),
+        Key::Kp5 => ('5', '5'),
+        Key::Kp6 => ('6', '6'),
+        Key::Kp7 => ('7', '7'),
+        Key::Kp8 => ('8', '8'),
+        Key::Kp9 => ('9', '9'),
+        Key::KpDot => ('.', '.'),
+        Key::KpPlus => ('+', '+'),
+        Key::KpMinus => ('-', '-'),
+        Key::KpAsterisk => ('*', '*'),
+        Key::KpSlash => ('/', '/'),
+        Key::KpEqual => ('=', '='),
+        _ => return None,
+    };
+    Some(pair)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHIFT: Modifiers = Modifiers {
+        shift: true,
+        ctrl: false,
+        alt: false,
+        caps_lock: false,
+    };
+
+    #[test]
+    fn test_letters() {
+        let none = Modifiers::default();
+        let caps = Modifiers {
+            caps_lock: true,
+            ..none
+        };
+
+        assert_eq!(Keymap::Us.text(Key::Q, &none), Some('q'));
+        assert_eq!(Keymap::Us.text(Key::Q, &SHIFT), Some('Q'));
+        assert_eq!(Keymap::Us.text(Key::Q, &caps), Some('Q'));
+        assert_eq!(
+            Keymap::Us.text(
+                Key::Q,
+                &Modifiers {
+                    shift: true,
+                    ..caps
+                }
+            ),
+            Some('q')
+        );
+        // Caps lock leaves digits alone
+        assert_eq!(Keymap::Us.text(Key::Digit1, &caps), Some('1'));
+    }
+
+    #[test]
+    fn test_us_and_uk_differ() {
+        for (key, us, uk) in [
+            (Key::Digit2, '@', '"'),
+            (Key::Digit3, '#', '£'),
+            (Key::Apostrophe, '"', '@'),
+            (Key::Backslash, '|', '~'),
+            (Key::NonUsBackslash, '|', '|'),
+            (Key::Slash, '?', '?'),
+        ] {
+            assert_eq!(Keymap::Us.text(key, &SHIFT), Some(us), "{:?}", key);
+            assert_eq!(Keymap::Uk.text(key, &SHIFT), Some(uk), "{:?}", key);
+        }
+    }
+
+    #[test]
+    fn test_nothing_typed() {
+        let ctrl = Modifiers {
+            ctrl: true,
+            ..Modifiers::default()
+        };
+
+        assert_eq!(Keymap::Uk.text(Key::C, &ctrl), None);
+        assert_eq!(Keymap::Uk.text(Key::Backspace, &Modifiers::default()), None);
+        assert_eq!(Keymap::Uk.text(Key::LeftShift, &SHIFT), None);
+        assert_eq!(Keymap::Uk.text(Key::Other(0x2ff), &SHIFT), None);
+    }
+}
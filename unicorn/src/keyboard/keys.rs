@@ -0,0 +1,136 @@
+// Codes from linux/input-event-codes.h
+
+codes! {
+    /// A key on a keyboard, by its Linux `KEY_*` code.
+    Key {
+        Esc = 1,
+        Digit1 = 2,
+        Digit2 = 3,
+        Digit3 = 4,
+        Digit4 = 5,
+        Digit5 = 6,
+        Digit6 = 7,
+        Digit7 = 8,
+        Digit8 = 9,
+        Digit9 = 10,
+        Digit0 = 11,
+        Minus = 12,
+        Equal = 13,
+        Backspace = 14,
+        Tab = 15,
+        Q = 16,
+        W = 17,
+        E = 18,
+        R = 19,
+        T = 20,
+        Y = 21,
+        U = 22,
+        I = 23,
+        O = 24,
+        P = 25,
+        LeftBrace = 26,
+        RightBrace = 27,
+        Enter = 28,
+        LeftCtrl = 29,
+        A = 30,
+        S = 31,
+        D = 32,
+        F = 33,
+        G = 34,
+        H = 35,
+        J = 36,
+        K = 37,
+        L = 38,
+        Semicolon = 39,
+        Apostrophe = 40,
+        Grave = 41,
+        LeftShift = 42,
+        Backslash = 43,
+        Z = 44,
+        X = 45,
+        C = 46,
+        V = 47,
+        B = 48,
+        N = 49,
+        M = 50,
+        Comma = 51,
+        Dot = 52,
+        Slash = 53,
+        RightShift = 54,
+        KpAsterisk = 55,
+        LeftAlt = 56,
+        Space = 57,
+        CapsLock = 58,
+        F1 = 59,
+        F2 = 60,
+        F3 = 61,
+        F4 = 62,
+        F5 = 63,
+        F6 = 64,
+        F7 = 65,
+        F8 = 66,
+        F9 = 67,
+        F10 = 68,
+        NumLock = 69,
+        ScrollLock = 70,
+        Kp7 = 71,
+        Kp8 = 72,
+        Kp9 = 73,
+        KpMinus = 74,
+        Kp4 = 75,
+        Kp5 = 76,
+        Kp6 = 77,
+        KpPlus = 78,
+        Kp1 = 79,
+        Kp2 = 80,
+        Kp3 = 81,
+        Kp0 = 82,
+        KpDot = 83,
+        /// The extra key beside left shift on ISO keyboards.
+        NonUsBackslash = 86,
+        F11 = 87,
+        F12 = 88,
+        KpEnter = 96,
+        RightCtrl = 97,
+        KpSlash = 98,
+        SysRq = 99,
+        RightAlt = 100,
+        Home = 102,
+        Up = 103,
+        PageUp = 104,
+        Left = 105,
+        Right = 106,
+        End = 107,
+        Down = 108,
+        PageDown = 109,
+        Insert = 110,
+        Delete = 111,
+        Mute = 113,
+        VolumeDown = 114,
+        VolumeUp = 115,
+        Power = 116,
+        KpEqual = 117,
+        Pause = 119,
+        LeftMeta = 125,
+        RightMeta = 126,
+        Compose = 127,
+        NextSong = 163,
+        PlayPause = 164,
+        PreviousSong = 165,
+        StopCd = 166,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_round_trip() {
+        for code in 0..0x300 {
+            assert_eq!(Key::from_code(code).code(), code);
+        }
+        assert_eq!(Key::from_code(30), Key::A);
+        assert_eq!(Key::from_code(0x110), Key::Other(0x110));
+    }
+}
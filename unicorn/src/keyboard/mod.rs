@@ -0,0 +1,191 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read},
+    mem,
+    os::unix::{
+        fs::OpenOptionsExt,
+        io::{AsRawFd, RawFd},
+    },
+    time::Duration,
+};
+
+use libc::input_event;
+use nix::{ioctl_read_buf, ioctl_write_int, request_code_read};
+use tokio::sync::mpsc::Receiver;
+
+use crate::error::{AppError, BoxedError, Error};
+
+pub mod capture;
+pub mod devices;
+pub mod event;
+pub mod keymap;
+pub mod keys;
+
+pub use capture::{Capture, CaptureEvent, CaptureOptions, DeviceMatch, Keyboards, ShutdownHandle};
+pub use devices::InputDeviceInfo;
+pub use event::{KeyEvent, KeyState, Modifiers};
+pub use keymap::Keymap;
+pub use keys::Key;
+
+ioctl_write_int!(eviocgrab, b'E', 0x90);
+ioctl_read_buf!(eviocgname, b'E', 0x06, u8);
+const SIZE_OF_INPUT_EVENT: usize = mem::size_of::<input_event>();
+
+/// Grab every keyboard, now and as they're plugged in, and send their key
+/// events. Text is decoded if a `keymap` is given. The keyboards are
+/// released once the receiver is dropped.
+pub fn grab_all_keyboards(keymap: Option<Keymap>) -> Receiver<KeyEvent> {
+    let (tx, rx) = tokio::sync::mpsc::channel::<KeyEvent>(16);
+
+    let options = CaptureOptions {
+        keymap,
+        ..Default::default()
+    };
+    match capture_keyboards(options) {
+        Ok(mut keyboards) => {
+            tokio::spawn(async move {
+                // Let go as soon as the receiver is dropped, not at the next key
+                loop {
+                    let event = tokio::select! {
+                        event = keyboards.next() => event,
+                        _ = tx.closed() => break,
+                    };
+                    match event {
+                        Some(CaptureEvent::Key(event)) => {
+                            if tx.send(event).await.is_err() {
+                                break;
+                            }
+                        }
+                        Some(_) => {}
+                        None => break,
+                    }
+                }
+                keyboards.shutdown().await;
+            });
+        }
+        Err(e) => log::error!("Failed to watch for keyboards: {}", e),
+    }
+
+    rx
+}
+
+/// Capture the keyboards chosen by `options`, now and as they're plugged
+/// in, and report their key events and comings and goings.
+pub fn capture_keyboards(options: CaptureOptions) -> Result<Keyboards, Error> {
+    Capture::start(options)
+}
+
+/// Wait up to `timeout` for `fd` to be readable. Fails if the device behind
+/// it has gone.
+fn wait_readable(fd: RawFd, timeout: Duration) -> io::Result<bool> {
+    let mut pollfd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout = timeout.as_millis().min(i32::MAX as u128) as i32;
+    match unsafe { libc::poll(&mut pollfd, 1, timeout) } {
+        -1 => {
+            let error = io::Error::last_os_error();
+            match error.kind() {
+                io::ErrorKind::Interrupted => Ok(false),
+                _ => Err(error),
+            }
+        }
+        0 => Ok(false),
+        _ if pollfd.revents & (libc::POLLERR | libc::POLLHUP | libc::POLLNVAL) != 0 => {
+            Err(io::Error::from_raw_os_error(libc::ENODEV))
+        }
+        _ => Ok(true),
+    }
+}
+
+pub(crate) struct InputDevice {
+    device_file: File,
+    buf: [u8; SIZE_OF_INPUT_EVENT],
+    grabbed: bool,
+}
+
+impl InputDevice {
+    pub fn open(device_file: &str) -> Result<Self, BoxedError> {
+        let device_file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(device_file)?;
+        Ok(InputDevice {
+            device_file,
+            buf: [0u8; SIZE_OF_INPUT_EVENT],
+            grabbed: false,
+        })
+    }
+
+    pub fn read_event(&mut self) -> Result<input_event, BoxedError> {
+        let num_bytes = self.device_file.read(&mut self.buf)?;
+        if num_bytes != SIZE_OF_INPUT_EVENT {
+            return Err(AppError::boxed("ShortRead"));
+        }
+        let event: input_event = unsafe { mem::transmute(self.buf) };
+        Ok(event)
+    }
+
+    /// Wait up to `timeout` for the next event.
+    pub fn next_event(&mut self, timeout: Duration) -> Result<Option<input_event>, BoxedError> {
+        if !wait_readable(self.device_file.as_raw_fd(), timeout)? {
+            return Ok(None);
+        }
+        match self.read_event() {
+            Ok(event) => Ok(Some(event)),
+            Err(e) => match e.downcast_ref::<io::Error>() {
+                Some(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Name the device reports for itself.
+    pub fn name(&self) -> Result<String, BoxedError> {
+        let mut buf = [0u8; 256];
+        let len = unsafe { eviocgname(self.device_file.as_raw_fd(), &mut buf)? } as usize;
+        let name = &buf[..len.min(buf.len())];
+        let name = name.split(|b| *b == 0).next().unwrap_or_default();
+        Ok(String::from_utf8_lossy(name).into_owned())
+    }
+
+    /// Range and current value of the absolute axis `axis`.
+    pub fn abs_info(&self, axis: u16) -> Result<libc::input_absinfo, BoxedError> {
+        let mut info: libc::input_absinfo = unsafe { mem::zeroed() };
+        let request = request_code_read!(
+            b'E',
+            0x40 + axis as u32,
+            mem::size_of::<libc::input_absinfo>()
+        );
+        if unsafe { libc::ioctl(self.device_file.as_raw_fd(), request as _, &mut info) } == -1 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(info)
+    }
+
+    pub fn grab(&mut self) -> Result<(), BoxedError> {
+        unsafe {
+            eviocgrab(self.device_file.as_raw_fd(), 1)?;
+        }
+        self.grabbed = true;
+        Ok(())
+    }
+
+    pub fn release(&mut self) -> Result<(), BoxedError> {
+        unsafe {
+            eviocgrab(self.device_file.as_raw_fd(), 0)?;
+        }
+        self.grabbed = false;
+        Ok(())
+    }
+}
+
+impl Drop for InputDevice {
+    fn drop(&mut self) {
+        if self.grabbed {
+            self.release().ok();
+        }
+    }
+}
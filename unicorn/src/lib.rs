@@ -1,3 +1,16 @@
+#[macro_use]
+mod codes;
+
+pub mod emulator;
 pub mod error;
+pub mod gamepad;
+#[cfg(feature = "embedded-graphics")]
+pub mod graphics;
+pub mod image;
 pub mod keyboard;
+pub mod net;
 pub mod pimoroni;
+pub mod text;
+
+#[cfg(test)]
+mod testing;
@@ -0,0 +1,405 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    ops::RangeInclusive,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::net::UdpSocket;
+
+use super::{show, DisplayHandle, DmxLayout};
+use crate::{error::Error, pimoroni::Display};
+
+// Art-Net 4 node, receiving DMX and answering discovery polls.
+
+pub const PORT: u16 = 6454;
+
+const ID: &[u8; 8] = b"Art-Net\0";
+const OP_POLL: u16 = 0x2000;
+const OP_POLL_REPLY: u16 = 0x2100;
+const OP_DMX: u16 = 0x5000;
+const OP_SYNC: u16 = 0x5200;
+const PROTOCOL_VERSION: u16 = 14;
+const POLL_REPLY_SIZE: usize = 239;
+const MAX_PACKET_SIZE: usize = 530;
+const PORT_TYPE_DMX_OUTPUT: u8 = 0x80;
+const GOOD_OUTPUT_DATA: u8 = 0x80;
+const STYLE_NODE: u8 = 0x00;
+/// Port-addresses are 15 bits.
+const MAX_UNIVERSE: u16 = 0x7fff;
+
+/// Frames go back to being shown as they arrive once syncs stop for this long.
+const SYNC_TIMEOUT: Duration = Duration::from_secs(4);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArtNetSettings {
+    /// 15 bit port-address of the first universe, the display spans as many
+    /// following universes as the layout needs.
+    pub universe: u16,
+    pub layout: DmxLayout,
+    /// Short name shown by lighting consoles.
+    pub name: String,
+}
+
+impl Default for ArtNetSettings {
+    fn default() -> Self {
+        ArtNetSettings {
+            universe: 0,
+            layout: DmxLayout::default(),
+            name: "Unicorn".to_string(),
+        }
+    }
+}
+
+enum Packet<'a> {
+    Poll,
+    Dmx { universe: u16, slots: &'a [u8] },
+    Sync,
+}
+
+fn parse(packet: &[u8]) -> Option<Packet<'_>> {
+    if packet.len() < 12 || &packet[..8] != ID {
+        return None;
+    }
+
+    match u16::from_le_bytes([packet[8], packet[9]]) {
+        OP_POLL => Some(Packet::Poll),
+        OP_SYNC => Some(Packet::Sync),
+        OP_DMX if packet.len() >= 18 => {
+            let universe = u16::from_le_bytes([packet[14], packet[15] & 0x7f]);
+            let len = u16::from_be_bytes([packet[16], packet[17]]) as usize;
+            let slots = packet.get(18..18 + len)?;
+            Some(Packet::Dmx { universe, slots })
+        }
+        _ => None,
+    }
+}
+
+/// Shows DMX sent to the display's universes, and makes it discoverable as a
+/// fixture by lighting consoles.
+pub struct ArtNetNode {
+    display: DisplayHandle,
+    settings: ArtNetSettings,
+    universes: RangeInclusive<u16>,
+    /// Set while syncs are arriving, until which frames wait for the next one.
+    synchronous_until: Option<Instant>,
+    pending: bool,
+}
+
+impl ArtNetNode {
+    /// Fails if the display would span port-addresses past the last, 32767.
+    pub fn new(display: Box<dyn Display + Send>, settings: ArtNetSettings) -> Result<Self, Error> {
+        let num_px = display.num_leds();
+        let universes = settings
+            .layout
+            .universes(settings.universe, num_px, MAX_UNIVERSE)?;
+        Ok(ArtNetNode {
+            display: Arc::new(Mutex::new(display)),
+            settings,
+            universes,
+            synchronous_until: None,
+            pending: false,
+        })
+    }
+
+    pub fn universes(&self) -> RangeInclusive<u16> {
+        self.universes.clone()
+    }
+
+    /// Listen for broadcasts on the standard port.
+    pub async fn bind(&self) -> Result<UdpSocket, Error> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, PORT))
+            .await
+            .map_err(Error::Io)?;
+        socket.set_broadcast(true).map_err(Error::Io)?;
+        Ok(socket)
+    }
+
+    pub async fn run(&mut self, socket: UdpSocket) -> Result<(), Error> {
+        let mut buf = [0; MAX_PACKET_SIZE];
+        loop {
+            let (len, from) = socket.recv_from(&mut buf).await.map_err(Error::Io)?;
+            if !self.handle_packet(&buf[..len], Instant::now()).await {
+                continue;
+            }
+
+            let ip = local_ip_towards(&socket, from).unwrap_or(Ipv4Addr::UNSPECIFIED);
+            for reply in self.poll_replies(ip) {
+                // The poller may have gone, which shouldn't take the node down
+                if let Err(e) = socket.send_to(&reply, from).await {
+                    log::warn!("Failed to reply to Art-Net poll from {}: {}", from, e);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Returns whether the packet was a poll which needs replies.
+    async fn handle_packet(&mut self, packet: &[u8], now: Instant) -> bool {
+        match parse(packet) {
+            Some(Packet::Poll) => return true,
+            Some(Packet::Dmx { universe, slots }) if self.universes().contains(&universe) => {
+                let offset = (universe - self.settings.universe) as usize;
+                self.settings
+                    .layout
+                    .write(self.display.lock().unwrap().as_mut(), offset, slots);
+
+                if self.synchronous_until.is_some_and(|until| now < until) {
+                    self.pending = true;
+                } else {
+                    self.synchronous_until = None;
+                    self.flush().await;
+                }
+            }
+            Some(Packet::Dmx { .. }) => {}
+            Some(Packet::Sync) => {
+                self.synchronous_until = Some(now + SYNC_TIMEOUT);
+                if self.pending {
+                    self.flush().await;
+                }
+            }
+            None => log::debug!("Ignoring packet which isn't Art-Net"),
+        }
+        false
+    }
+
+    async fn flush(&mut self) {
+        self.pending = false;
+        if let Err(e) = show(&self.display, |display| display.flush()).await {
+            log::error!("Failed to show Art-Net frame: {}", e);
+        }
+    }
+
+    /// One reply for each universe, bound to the node's IP as ports 1, 2...
+    fn poll_replies(&self, ip: Ipv4Addr) -> Vec<Vec<u8>> {
+        let dims = *self.display.lock().unwrap().dimensions();
+        let long_name = format!(
+            "Unicorn {}x{} LED matrix, {} pixels",
+            dims.width,
+            dims.height,
+            dims.num_px()
+        );
+        let report = format!("#0001 [0000] {} pixels", dims.num_px());
+
+        self.universes()
+            .enumerate()
+            .map(|(i, universe)| {
+                let [sub_uni, net] = universe.to_le_bytes();
+                let mut reply = vec![0; POLL_REPLY_SIZE];
+                reply[..8].copy_from_slice(ID);
+                reply[8..10].copy_from_slice(&OP_POLL_REPLY.to_le_bytes());
+                reply[10..14].copy_from_slice(&ip.octets());
+                reply[14..16].copy_from_slice(&PORT.to_le_bytes());
+                reply[16..18].copy_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+                reply[18] = net;
+                reply[19] = sub_uni >> 4;
+                copy_str(&mut reply[26..44], &self.settings.name);
+                copy_str(&mut reply[44..108], &long_name);
+                copy_str(&mut reply[108..172], &report);
+                reply[172..174].copy_from_slice(&1u16.to_be_bytes());
+                reply[174] = PORT_TYPE_DMX_OUTPUT;
+                reply[182] = GOOD_OUTPUT_DATA;
+                reply[190] = sub_uni & 0x0f;
+                reply[200] = STYLE_NODE;
+                reply[207..211].copy_from_slice(&ip.octets());
+                reply[211] = i as u8 + 1;
+                reply
+            })
+            .collect()
+    }
+}
+
+/// Copy as much of `s` as fits, leaving room for a null terminator.
+fn copy_str(field: &mut [u8], s: &str) {
+    let len = s.len().min(field.len() - 1);
+    field[..len].copy_from_slice(&s.as_bytes()[..len]);
+}
+
+/// Address the node is reachable on from `peer`, for advertising in replies.
+fn local_ip_towards(socket: &UdpSocket, peer: SocketAddr) -> Option<Ipv4Addr> {
+    let bound = match socket.local_addr().ok()? {
+        SocketAddr::V4(addr) => *addr.ip(),
+        SocketAddr::V6(_) => return None,
+    };
+    if !bound.is_unspecified() {
+        return Some(bound);
+    }
+
+    // Connecting a UDP socket sends nothing, but picks the outgoing interface
+    let probe = std::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    probe.connect(peer).ok()?;
+    match probe.local_addr().ok()? {
+        SocketAddr::V4(addr) => Some(*addr.ip()),
+        SocketAddr::V6(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rgb::RGB8;
+
+    use super::*;
+    use crate::{net::PixelOrder, pimoroni::Dimensions, testing::SharedDisplay};
+
+    fn header(opcode: u16) -> Vec<u8> {
+        let mut packet = ID.to_vec();
+        packet.extend(opcode.to_le_bytes());
+        packet.extend(PROTOCOL_VERSION.to_be_bytes());
+        packet
+    }
+
+    fn dmx(universe: u16, slots: &[u8]) -> Vec<u8> {
+        let mut packet = header(OP_DMX);
+        let [sub_uni, net] = universe.to_le_bytes();
+        packet.extend([0, 0, sub_uni, net]);
+        packet.extend((slots.len() as u16).to_be_bytes());
+        packet.extend(slots);
+        packet
+    }
+
+    fn node() -> (SharedDisplay, ArtNetNode) {
+        let display = SharedDisplay::new(Dimensions {
+            width: 3,
+            height: 1,
+        });
+        let settings = ArtNetSettings {
+            universe: 0x10f,
+            layout: DmxLayout {
+                start_channel: 1,
+                pixels_per_universe: 2,
+                order: PixelOrder::Rgb,
+            },
+            ..Default::default()
+        };
+        (
+            display.clone(),
+            ArtNetNode::new(Box::new(display), settings).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_dmx_maps_port_addresses() {
+        let (display, mut node) = node();
+        let now = Instant::now();
+        assert_eq!(node.universes(), 0x10f..=0x110);
+
+        node.handle_packet(&dmx(0x10f, &[1, 2, 3, 4, 5, 6]), now)
+            .await;
+        node.handle_packet(&dmx(0x110, &[7, 8, 9]), now).await;
+        node.handle_packet(&dmx(0x111, &[9; 6]), now).await;
+        node.handle_packet(&dmx(0x00f, &[9; 6]), now).await;
+
+        assert_eq!(
+            display.pixels(),
+            [RGB8::new(1, 2, 3), RGB8::new(4, 5, 6), RGB8::new(7, 8, 9)]
+        );
+        assert_eq!(display.flushes(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_sync_holds_frames() {
+        let (display, mut node) = node();
+        let now = Instant::now();
+
+        node.handle_packet(&header(OP_SYNC), now).await;
+        node.handle_packet(&dmx(0x10f, &[1; 6]), now).await;
+        node.handle_packet(&dmx(0x110, &[1; 3]), now).await;
+        assert_eq!(display.flushes(), 0);
+
+        node.handle_packet(&header(OP_SYNC), now).await;
+        assert_eq!(display.flushes(), 1);
+        assert_eq!(display.pixels(), [RGB8::new(1, 1, 1); 3]);
+
+        // Without syncs the node falls back to showing frames straight away
+        node.handle_packet(&dmx(0x10f, &[2; 6]), now + SYNC_TIMEOUT)
+            .await;
+        assert_eq!(display.flushes(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_poll_replies_advertise_pixels() {
+        let (_, mut node) = node();
+        assert!(node.handle_packet(&header(OP_POLL), Instant::now()).await);
+
+        let replies = node.poll_replies(Ipv4Addr::new(10, 0, 0, 7));
+        assert_eq!(replies.len(), 2);
+
+        let reply = &replies[1];
+        assert_eq!(reply.len(), POLL_REPLY_SIZE);
+        assert_eq!(&reply[..10], b"Art-Net\0\x00\x21");
+        assert_eq!(reply[10..16], [10, 0, 0, 7, 0x36, 0x19]);
+        assert_eq!((reply[18], reply[19], reply[190]), (1, 1, 0));
+        assert_eq!(reply[211], 2);
+
+        let long_name = String::from_utf8_lossy(&reply[44..108]);
+        assert!(long_name.starts_with("Unicorn 3x1 LED matrix, 3 pixels\0"));
+        assert!(reply[26..44].starts_with(b"Unicorn\0"));
+    }
+
+    #[test]
+    fn test_rejects_port_addresses_past_the_last() {
+        let display = SharedDisplay::new(Dimensions {
+            width: 3,
+            height: 1,
+        });
+        let new = |universe| {
+            let (_, node) = node();
+            ArtNetNode::new(
+                Box::new(display.clone()),
+                ArtNetSettings {
+                    universe,
+                    ..node.settings
+                },
+            )
+        };
+
+        assert_eq!(new(0x7ffe).unwrap().universes(), 0x7ffe..=0x7fff);
+        assert!(matches!(
+            new(0x7fff),
+            Err(Error::UniverseOutOfRange { count: 2, .. })
+        ));
+        assert!(new(u16::MAX).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ignores_other_packets() {
+        let (display, mut node) = node();
+        let mut truncated = dmx(0x10f, &[1; 6]);
+        truncated.pop();
+
+        assert!(!node.handle_packet(&truncated, Instant::now()).await);
+        assert!(
+            !node
+                .handle_packet(b"Art-Net\0\x00\x99\x00\x0e", Instant::now())
+                .await
+        );
+        assert!(
+            !node
+                .handle_packet(b"Not-Art\0\x00\x20\x00\x0e", Instant::now())
+                .await
+        );
+        assert_eq!(display.flushes(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_loopback() {
+        let (display, mut node) = node();
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move { node.run(socket).await });
+
+        let console = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        console.send_to(&header(OP_POLL), addr).await.unwrap();
+        let mut reply = [0; POLL_REPLY_SIZE];
+        for port in 1..=2 {
+            let (len, _) = console.recv_from(&mut reply).await.unwrap();
+            assert_eq!(len, POLL_REPLY_SIZE);
+            assert_eq!(reply[10..14], [127, 0, 0, 1]);
+            assert_eq!(reply[211], port);
+        }
+
+        console.send_to(&dmx(0x110, &[5; 3]), addr).await.unwrap();
+        display.wait_for_flushes(1).await;
+        assert_eq!(display.pixels()[2], RGB8::new(5, 5, 5));
+    }
+}
@@ -0,0 +1,159 @@
+// Receivers which let lighting software on other machines drive a display.
+
+use std::{
+    io,
+    ops::RangeInclusive,
+    sync::{Arc, Mutex},
+};
+
+use rgb::RGB8;
+
+use crate::{error::Error, pimoroni::Display};
+
+pub mod artnet;
+pub mod opc;
+pub mod realtime;
+pub mod sacn;
+
+pub(crate) type DisplayHandle = Arc<Mutex<Box<dyn Display + Send>>>;
+
+/// Run `draw` on the display on a blocking thread. Flushing blocks on the
+/// hardware, so receivers do it this way to keep it off the runtime.
+pub(crate) async fn show<F>(display: &DisplayHandle, draw: F) -> Result<(), Error>
+where
+    F: FnOnce(&mut dyn Display) -> Result<(), Error> + Send + 'static,
+{
+    let display = display.clone();
+    tokio::task::spawn_blocking(move || draw(display.lock().unwrap().as_mut()))
+        .await
+        .map_err(|e| Error::Io(io::Error::other(e)))?
+}
+
+/// Write packed RGB triples to consecutive LEDs from `start`, dropping any
+/// which fall off the end of the display.
+pub(crate) fn write_rgb(display: &mut dyn Display, start: usize, data: &[u8]) {
+    let num_px = display.num_leds();
+    for (idx, rgb) in (start..num_px).zip(data.chunks_exact(3)) {
+        display.set_idx(idx, &RGB8::new(rgb[0], rgb[1], rgb[2]));
+    }
+}
+
+/// Turn every LED off.
+pub(crate) fn blank(display: &mut dyn Display) -> Result<(), Error> {
+    for idx in 0..display.num_leds() {
+        display.set_idx(idx, &RGB8::default());
+    }
+    display.flush()
+}
+
+/// Order of the three channels making up each pixel in a DMX universe.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PixelOrder {
+    Rgb,
+    Rbg,
+    Grb,
+    Gbr,
+    Brg,
+    Bgr,
+}
+
+impl PixelOrder {
+    fn to_rgb(self, [a, b, c]: [u8; 3]) -> RGB8 {
+        match self {
+            PixelOrder::Rgb => RGB8::new(a, b, c),
+            PixelOrder::Rbg => RGB8::new(a, c, b),
+            PixelOrder::Grb => RGB8::new(b, a, c),
+            PixelOrder::Gbr => RGB8::new(c, a, b),
+            PixelOrder::Brg => RGB8::new(b, c, a),
+            PixelOrder::Bgr => RGB8::new(c, b, a),
+        }
+    }
+}
+
+/// How the display's LEDs are spread over consecutive DMX universes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DmxLayout {
+    /// DMX channel, counting from 1, of the first pixel in each universe.
+    pub start_channel: u16,
+    /// Pixels carried by each universe before moving on to the next.
+    pub pixels_per_universe: usize,
+    pub order: PixelOrder,
+}
+
+impl Default for DmxLayout {
+    fn default() -> Self {
+        DmxLayout {
+            start_channel: 1,
+            pixels_per_universe: 170,
+            order: PixelOrder::Rgb,
+        }
+    }
+}
+
+impl DmxLayout {
+    pub fn universe_count(&self, num_px: usize) -> usize {
+        num_px.div_ceil(self.pixels_per_universe.max(1))
+    }
+
+    /// Universes from `first` spanned by a display of `num_px` pixels, or an
+    /// error if they'd run past `max`.
+    pub(crate) fn universes(
+        &self,
+        first: u16,
+        num_px: usize,
+        max: u16,
+    ) -> Result<RangeInclusive<u16>, Error> {
+        let count = self.universe_count(num_px).max(1);
+        u16::try_from(count - 1)
+            .ok()
+            .and_then(|extra| first.checked_add(extra))
+            .filter(|last| *last <= max)
+            .map(|last| first..=last)
+            .ok_or(Error::UniverseOutOfRange { first, count, max })
+    }
+
+    /// Write the DMX slots (without the start code) of the `offset`th
+    /// universe spanned by the display.
+    pub(crate) fn write(&self, display: &mut dyn Display, offset: usize, slots: &[u8]) {
+        let num_px = display.num_leds();
+        let first = offset * self.pixels_per_universe;
+        let slots = slots
+            .get(self.start_channel.max(1) as usize - 1..)
+            .unwrap_or_default();
+
+        for (idx, channels) in (first..num_px)
+            .zip(slots.chunks_exact(3))
+            .take(self.pixels_per_universe)
+        {
+            let rgb = self.order.to_rgb([channels[0], channels[1], channels[2]]);
+            display.set_idx(idx, &rgb);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pimoroni::Dimensions, testing::SharedDisplay};
+
+    #[test]
+    fn test_dmx_layout() {
+        let mut display = SharedDisplay::new(Dimensions {
+            width: 3,
+            height: 1,
+        });
+        let layout = DmxLayout {
+            start_channel: 2,
+            pixels_per_universe: 2,
+            order: PixelOrder::Grb,
+        };
+        assert_eq!(layout.universe_count(3), 2);
+
+        layout.write(&mut display, 0, &[9, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        layout.write(&mut display, 1, &[9, 7, 8, 9, 1, 1, 1]);
+        assert_eq!(
+            display.pixels(),
+            [RGB8::new(2, 1, 3), RGB8::new(5, 4, 6), RGB8::new(8, 7, 9)]
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+use tokio::{
+    io::AsyncReadExt,
+    net::{TcpListener, TcpStream},
+};
+
+use super::{show, write_rgb, DisplayHandle};
+use crate::{error::Error, pimoroni::Display};
+
+// Open Pixel Control: http://openpixelcontrol.org/
+
+pub const DEFAULT_PORT: u16 = 7890;
+
+const BROADCAST_CHANNEL: u8 = 0;
+const CMD_SET_PIXEL_COLOURS: u8 = 0;
+const CMD_SYSTEM_EXCLUSIVE: u8 = 255;
+
+/// Shows frames sent by OPC clients, from any number of connections.
+pub struct OpcServer {
+    display: DisplayHandle,
+    channel: u8,
+}
+
+impl OpcServer {
+    /// Accept set-pixel-colours messages on `channel` as well as the
+    /// broadcast channel 0.
+    pub fn new(display: Box<dyn Display + Send>, channel: u8) -> Self {
+        OpcServer {
+            display: Arc::new(Mutex::new(display)),
+            channel,
+        }
+    }
+
+    pub async fn run(&self, listener: TcpListener) -> Result<(), Error> {
+        loop {
+            let (stream, peer) = listener.accept().await.map_err(Error::Io)?;
+            log::info!("OPC client connected from {}", peer);
+
+            let display = self.display.clone();
+            let channel = self.channel;
+            tokio::spawn(async move {
+                match handle_client(stream, display, channel).await {
+                    Ok(()) => log::info!("OPC client {} disconnected", peer),
+                    Err(e) => log::warn!("Dropped OPC client {}: {}", peer, e),
+                }
+            });
+        }
+    }
+}
+
+async fn handle_client(
+    mut stream: TcpStream,
+    display: DisplayHandle,
+    channel: u8,
+) -> Result<(), io::Error> {
+    let mut header = [0; 4];
+    loop {
+        match stream.read_exact(&mut header).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        }
+        let [msg_channel, command, len_hi, len_lo] = header;
+
+        let mut data = vec![0; u16::from_be_bytes([len_hi, len_lo]) as usize];
+        stream.read_exact(&mut data).await?;
+
+        match command {
+            CMD_SET_PIXEL_COLOURS if msg_channel == BROADCAST_CHANNEL || msg_channel == channel => {
+                let shown = show(&display, move |display| {
+                    write_rgb(display, 0, &data);
+                    display.flush()
+                });
+                if let Err(e) = shown.await {
+                    log::error!("Failed to show OPC frame: {}", e);
+                }
+            }
+            CMD_SET_PIXEL_COLOURS | CMD_SYSTEM_EXCLUSIVE => {}
+            _ => log::debug!("Ignoring unknown OPC command {}", command),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rgb::RGB8;
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+    use crate::{pimoroni::Dimensions, testing};
+
+    fn message(channel: u8, command: u8, data: &[u8]) -> Vec<u8> {
+        let mut msg = vec![channel, command];
+        msg.extend((data.len() as u16).to_be_bytes());
+        msg.extend(data);
+        msg
+    }
+
+    async fn start(channel: u8) -> (testing::SharedDisplay, TcpStream) {
+        let display = testing::SharedDisplay::new(Dimensions {
+            width: 2,
+            height: 1,
+        });
+        let server = OpcServer::new(Box::new(display.clone()), channel);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { server.run(listener).await });
+
+        (display, TcpStream::connect(addr).await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_set_pixel_colours() {
+        let (display, mut client) = start(1).await;
+
+        // Extra pixels beyond the display are dropped
+        let msg = message(1, CMD_SET_PIXEL_COLOURS, &[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        client.write_all(&msg).await.unwrap();
+        display.wait_for_flushes(1).await;
+
+        assert_eq!(display.pixels(), [RGB8::new(1, 2, 3), RGB8::new(4, 5, 6)]);
+    }
+
+    #[tokio::test]
+    async fn test_only_own_and_broadcast_channels_are_shown() {
+        let (display, mut client) = start(1).await;
+
+        for msg in [
+            message(2, CMD_SET_PIXEL_COLOURS, &[9, 9, 9]),
+            message(1, CMD_SYSTEM_EXCLUSIVE, &[0, 1, 0, 0]),
+            message(1, 7, &[9, 9, 9]),
+            // A short frame only updates the pixels it covers
+            message(BROADCAST_CHANNEL, CMD_SET_PIXEL_COLOURS, &[1, 1, 1]),
+        ] {
+            client.write_all(&msg).await.unwrap();
+        }
+        display.wait_for_flushes(1).await;
+
+        assert_eq!(display.pixels(), [RGB8::new(1, 1, 1), RGB8::default()]);
+        assert_eq!(display.flushes(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_serves_several_clients() {
+        let (display, mut first) = start(1).await;
+        let addr = first.peer_addr().unwrap();
+        let mut second = TcpStream::connect(addr).await.unwrap();
+
+        first
+            .write_all(&message(1, CMD_SET_PIXEL_COLOURS, &[1, 1, 1]))
+            .await
+            .unwrap();
+        display.wait_for_flushes(1).await;
+        second
+            .write_all(&message(1, CMD_SET_PIXEL_COLOURS, &[2, 2, 2]))
+            .await
+            .unwrap();
+        display.wait_for_flushes(2).await;
+
+        assert_eq!(display.pixels()[0], RGB8::new(2, 2, 2));
+    }
+}
@@ -0,0 +1,595 @@
+use std::{
+    net::Ipv4Addr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use rgb::RGB8;
+use tokio::net::UdpSocket;
+
+use super::{show, write_rgb, DisplayHandle};
+use crate::{
+    error::Error,
+    pimoroni::{Dimensions, Display},
+};
+
+// Realtime UDP protocols pushed by ambient lighting tools: DDP
+// (http://www.3waylabs.com/ddp/) and WLED's WARLS, DRGB and DNRGB.
+
+pub const DDP_PORT: u16 = 4048;
+pub const WLED_PORT: u16 = 21324;
+/// How long the last frame stays up once packets stop, unless the sender
+/// asks for something else.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(2500);
+
+const DDP_VERSION_MASK: u8 = 0xc0;
+const DDP_VERSION_1: u8 = 0x40;
+const DDP_TIMECODE: u8 = 0x10;
+const DDP_REPLY: u8 = 0x04;
+const DDP_QUERY: u8 = 0x02;
+const DDP_PUSH: u8 = 0x01;
+const DDP_ID_DISPLAY: u8 = 1;
+const DDP_ID_ALL: u8 = 255;
+const DDP_MAX_PACKET_SIZE: usize = 1500;
+
+const WLED_WARLS: u8 = 1;
+const WLED_DRGB: u8 = 2;
+const WLED_DNRGB: u8 = 4;
+const WLED_NO_TIMEOUT: u8 = 255;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    Ddp,
+    /// WLED's realtime protocols, told apart by their first byte.
+    Wled,
+}
+
+impl Protocol {
+    pub fn port(&self) -> u16 {
+        match self {
+            Protocol::Ddp => DDP_PORT,
+            Protocol::Wled => WLED_PORT,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Live {
+    Idle,
+    Until(Instant),
+    Forever,
+}
+
+/// Everything drawn on the background since it was last reset, kept as it
+/// was addressed so it can be replayed onto the display in the same order.
+/// Replaying by index and by position separately means it doesn't matter
+/// how the display maps one to the other.
+struct Drawn {
+    dims: Dimensions,
+    /// Colour last set at each LED index, with when it was set.
+    by_idx: Vec<Option<(u64, RGB8)>>,
+    /// Colour last set at each position, row by row, with when it was set.
+    by_xy: Vec<Option<(u64, RGB8)>>,
+    next: u64,
+}
+
+impl Drawn {
+    fn new(display: &dyn Display) -> Self {
+        let dims = *display.dimensions();
+        Drawn {
+            dims,
+            by_idx: vec![None; display.num_leds()],
+            by_xy: vec![None; dims.num_px()],
+            next: 0,
+        }
+    }
+
+    fn set_xy(&mut self, x: usize, y: usize, rgb: &RGB8) {
+        assert!(x < self.dims.width, "LED x index out of range: {}", x);
+        assert!(y < self.dims.height, "LED y index out of range: {}", y);
+        self.by_xy[x + y * self.dims.width] = Some((self.next, *rgb));
+        self.next += 1;
+    }
+
+    fn set_idx(&mut self, idx: usize, rgb: &RGB8) {
+        assert!(idx < self.by_idx.len(), "LED index out of range: {}", idx);
+        self.by_idx[idx] = Some((self.next, *rgb));
+        self.next += 1;
+    }
+
+    fn clear(&mut self) {
+        self.by_idx.fill(None);
+        self.by_xy.fill(None);
+        self.next = 0;
+    }
+
+    /// Blank `display`, then draw everything on it again.
+    fn replay(&self, display: &mut dyn Display) {
+        for idx in 0..self.by_idx.len() {
+            display.set_idx(idx, &RGB8::default());
+        }
+
+        enum At {
+            Idx(usize),
+            Xy(usize, usize),
+        }
+        let by_idx = self.by_idx.iter().enumerate().map(|(i, d)| (At::Idx(i), d));
+        let width = self.dims.width;
+        let by_xy = self
+            .by_xy
+            .iter()
+            .enumerate()
+            .map(|(i, d)| (At::Xy(i % width, i / width), d));
+        let mut drawn = by_idx
+            .chain(by_xy)
+            .filter_map(|(at, d)| d.map(|(when, rgb)| (when, at, rgb)))
+            .collect::<Vec<_>>();
+        drawn.sort_by_key(|(when, ..)| *when);
+
+        for (_, at, rgb) in drawn {
+            match at {
+                At::Idx(idx) => display.set_idx(idx, &rgb),
+                At::Xy(x, y) => display.set_xy(x, y, &rgb),
+            }
+        }
+    }
+}
+
+/// Kept apart from the display, so the display can be flushed without
+/// holding up the background.
+struct State {
+    /// What the display shows when no realtime frames are coming in.
+    background: Drawn,
+    live: Live,
+}
+
+impl State {
+    fn go_live(&mut self, live: Live) {
+        if self.live == Live::Idle {
+            log::info!("Showing realtime frames");
+        }
+        self.live = live;
+    }
+
+    /// Returns whether the background was put back and needs flushing.
+    fn expire(&mut self, display: &mut dyn Display, now: Instant) -> bool {
+        if !matches!(self.live, Live::Until(until) if now >= until) {
+            return false;
+        }
+
+        log::info!("Realtime frames stopped, restoring the display");
+        self.live = Live::Idle;
+        self.background.replay(display);
+        true
+    }
+
+    /// Returns whether the frame is complete and needs flushing.
+    fn handle_ddp(
+        &mut self,
+        display: &mut dyn Display,
+        packet: &[u8],
+        now: Instant,
+        timeout: Duration,
+    ) -> bool {
+        if packet.len() < 10 || packet[0] & DDP_VERSION_MASK != DDP_VERSION_1 {
+            log::debug!("Ignoring packet which isn't DDP version 1");
+            return false;
+        }
+        let flags = packet[0];
+        if flags & (DDP_QUERY | DDP_REPLY) != 0 || !matches!(packet[3], DDP_ID_DISPLAY | DDP_ID_ALL)
+        {
+            return false;
+        }
+
+        let offset = u32::from_be_bytes(packet[4..8].try_into().unwrap()) as usize;
+        let len = u16::from_be_bytes([packet[8], packet[9]]) as usize;
+        let header = if flags & DDP_TIMECODE != 0 { 14 } else { 10 };
+        let data = match packet.get(header..header + len) {
+            Some(data) if offset % 3 == 0 => data,
+            _ => {
+                log::debug!("Ignoring malformed DDP packet");
+                return false;
+            }
+        };
+
+        self.go_live(Live::Until(now + timeout));
+        write_rgb(display, offset / 3, data);
+        flags & DDP_PUSH != 0
+    }
+
+    /// Returns whether the frame needs flushing.
+    fn handle_wled(&mut self, display: &mut dyn Display, packet: &[u8], now: Instant) -> bool {
+        if packet.len() < 2 {
+            return false;
+        }
+        let live = match packet[1] {
+            WLED_NO_TIMEOUT => Live::Forever,
+            secs => Live::Until(now + Duration::from_secs(secs as u64)),
+        };
+        let data = &packet[2..];
+
+        match packet[0] {
+            WLED_WARLS => {
+                self.go_live(live);
+                let num_px = display.num_leds();
+                for led in data
+                    .chunks_exact(4)
+                    .filter(|led| (led[0] as usize) < num_px)
+                {
+                    let rgb = RGB8::new(led[1], led[2], led[3]);
+                    display.set_idx(led[0] as usize, &rgb);
+                }
+            }
+            WLED_DRGB => {
+                self.go_live(live);
+                write_rgb(display, 0, data);
+            }
+            WLED_DNRGB if data.len() >= 2 => {
+                self.go_live(live);
+                let start = u16::from_be_bytes([data[0], data[1]]) as usize;
+                write_rgb(display, start, &data[2..]);
+            }
+            protocol => {
+                log::debug!("Ignoring WLED protocol {}", protocol);
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Shows frames pushed over DDP or WLED's realtime protocols, going back to
+/// the display's previous content when they stop.
+///
+/// Anything else to be shown should be drawn on the [`Background`], which
+/// holds it back while realtime frames are coming in.
+pub struct RealtimeReceiver {
+    display: DisplayHandle,
+    state: Arc<Mutex<State>>,
+    timeout: Duration,
+}
+
+impl RealtimeReceiver {
+    /// `timeout` applies to DDP, WLED senders choose their own.
+    pub fn new(display: Box<dyn Display + Send>, timeout: Duration) -> Self {
+        let background = Drawn::new(display.as_ref());
+        RealtimeReceiver {
+            display: Arc::new(Mutex::new(display)),
+            state: Arc::new(Mutex::new(State {
+                background,
+                live: Live::Idle,
+            })),
+            timeout,
+        }
+    }
+
+    pub fn background(&self) -> Background {
+        let dims = *self.display.lock().unwrap().dimensions();
+        Background {
+            display: self.display.clone(),
+            state: self.state.clone(),
+            dims,
+        }
+    }
+
+    /// Listen on the protocol's standard port.
+    pub async fn bind(protocol: Protocol) -> Result<UdpSocket, Error> {
+        UdpSocket::bind((Ipv4Addr::UNSPECIFIED, protocol.port()))
+            .await
+            .map_err(Error::Io)
+    }
+
+    /// Receive `protocol` on `socket`. Runs alongside other calls, so one
+    /// receiver can take both protocols.
+    pub async fn run(&self, socket: UdpSocket, protocol: Protocol) -> Result<(), Error> {
+        let mut buf = [0; DDP_MAX_PACKET_SIZE];
+        let period = (self.timeout / 4).clamp(Duration::from_millis(1), Duration::from_millis(250));
+        let mut expiry = tokio::time::interval(period);
+
+        loop {
+            tokio::select! {
+                received = socket.recv(&mut buf) => {
+                    let len = received.map_err(Error::Io)?;
+                    self.handle_packet(protocol, &buf[..len], Instant::now()).await;
+                }
+                _ = expiry.tick() => self.expire(Instant::now()).await,
+            }
+        }
+    }
+
+    async fn handle_packet(&self, protocol: Protocol, packet: &[u8], now: Instant) {
+        let complete = {
+            let mut state = self.state.lock().unwrap();
+            let mut display = self.display.lock().unwrap();
+            match protocol {
+                Protocol::Ddp => state.handle_ddp(display.as_mut(), packet, now, self.timeout),
+                Protocol::Wled => state.handle_wled(display.as_mut(), packet, now),
+            }
+        };
+        if complete {
+            self.flush().await;
+        }
+    }
+
+    async fn expire(&self, now: Instant) {
+        let restored = {
+            let mut state = self.state.lock().unwrap();
+            state.expire(self.display.lock().unwrap().as_mut(), now)
+        };
+        if restored {
+            self.flush().await;
+        }
+    }
+
+    async fn flush(&self) {
+        if let Err(e) = show(&self.display, |display| display.flush()).await {
+            log::error!("Failed to show realtime frame: {}", e);
+        }
+    }
+}
+
+/// The display as seen by everything other than the realtime senders.
+#[derive(Clone)]
+pub struct Background {
+    display: DisplayHandle,
+    state: Arc<Mutex<State>>,
+    dims: Dimensions,
+}
+
+impl Background {
+    fn is_shown(&self) -> bool {
+        self.state.lock().unwrap().live == Live::Idle
+    }
+}
+
+impl Display for Background {
+    fn set_xy(&mut self, x: usize, y: usize, rgb: &RGB8) {
+        let mut state = self.state.lock().unwrap();
+        state.background.set_xy(x, y, rgb);
+        if state.live == Live::Idle {
+            self.display.lock().unwrap().set_xy(x, y, rgb);
+        }
+    }
+
+    fn set_idx(&mut self, idx: usize, rgb: &RGB8) {
+        let mut state = self.state.lock().unwrap();
+        state.background.set_idx(idx, rgb);
+        if state.live == Live::Idle {
+            self.display.lock().unwrap().set_idx(idx, rgb);
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        if self.is_shown() {
+            self.display.lock().unwrap().flush()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        self.state.lock().unwrap().background.clear();
+        if self.is_shown() {
+            self.display.lock().unwrap().reset()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn dimensions(&self) -> &Dimensions {
+        &self.dims
+    }
+
+    fn set_brightness(&mut self, brightness: f32) {
+        self.display.lock().unwrap().set_brightness(brightness);
+    }
+
+    fn brightness(&self) -> f32 {
+        self.display.lock().unwrap().brightness()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pimoroni::pixelmap::{Mapped, Order, Serpentine},
+        testing::SharedDisplay,
+    };
+
+    const TIMEOUT: Duration = Duration::from_millis(50);
+
+    fn receiver() -> (SharedDisplay, RealtimeReceiver) {
+        let display = SharedDisplay::new(Dimensions {
+            width: 3,
+            height: 1,
+        });
+        let receiver = RealtimeReceiver::new(Box::new(display.clone()), TIMEOUT);
+        (display, receiver)
+    }
+
+    fn ddp(flags: u8, offset: u32, data: &[u8]) -> Vec<u8> {
+        let mut packet = vec![DDP_VERSION_1 | flags, 0, 0x0b, DDP_ID_DISPLAY];
+        packet.extend(offset.to_be_bytes());
+        packet.extend((data.len() as u16).to_be_bytes());
+        if flags & DDP_TIMECODE != 0 {
+            packet.extend([0; 4]);
+        }
+        packet.extend(data);
+        packet
+    }
+
+    fn grey(v: u8) -> RGB8 {
+        RGB8::new(v, v, v)
+    }
+
+    #[tokio::test]
+    async fn test_ddp_shows_frame_on_push() {
+        let (display, receiver) = receiver();
+        let now = Instant::now();
+
+        receiver
+            .handle_packet(Protocol::Ddp, &ddp(0, 3, &[1, 2, 3, 4, 5, 6]), now)
+            .await;
+        assert_eq!(display.flushes(), 0);
+        receiver
+            .handle_packet(Protocol::Ddp, &ddp(DDP_PUSH, 0, &[7, 8, 9]), now)
+            .await;
+
+        assert_eq!(display.flushes(), 1);
+        assert_eq!(
+            display.pixels(),
+            [RGB8::new(7, 8, 9), RGB8::new(1, 2, 3), RGB8::new(4, 5, 6)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ddp_ignores_other_packets() {
+        let (display, receiver) = receiver();
+        let now = Instant::now();
+
+        let mut other_device = ddp(DDP_PUSH, 0, &[1; 3]);
+        other_device[3] = 2;
+        let mut truncated = ddp(DDP_PUSH, 0, &[1; 3]);
+        truncated.pop();
+        for packet in [
+            other_device,
+            truncated,
+            ddp(DDP_PUSH | DDP_QUERY, 0, &[1; 3]),
+            ddp(DDP_PUSH, 1, &[1; 3]),
+            vec![0x80, 0, 0, 1, 0, 0, 0, 0, 0, 0],
+        ] {
+            receiver.handle_packet(Protocol::Ddp, &packet, now).await;
+        }
+        assert_eq!(display.flushes(), 0);
+
+        receiver
+            .handle_packet(
+                Protocol::Ddp,
+                &ddp(DDP_PUSH | DDP_TIMECODE, 6, &[1; 3]),
+                now,
+            )
+            .await;
+        assert_eq!(display.pixels()[2], grey(1));
+    }
+
+    #[tokio::test]
+    async fn test_wled_protocols() {
+        let (display, receiver) = receiver();
+        let now = Instant::now();
+
+        receiver
+            .handle_packet(Protocol::Wled, &[WLED_DRGB, 2, 1, 1, 1, 2, 2, 2], now)
+            .await;
+        assert_eq!(display.pixels(), [grey(1), grey(2), grey(0)]);
+
+        receiver
+            .handle_packet(
+                Protocol::Wled,
+                &[WLED_WARLS, 2, 2, 3, 3, 3, 9, 9, 9, 9],
+                now,
+            )
+            .await;
+        assert_eq!(display.pixels(), [grey(1), grey(2), grey(3)]);
+
+        receiver
+            .handle_packet(Protocol::Wled, &[WLED_DNRGB, 2, 0, 1, 4, 4, 4], now)
+            .await;
+        assert_eq!(display.pixels(), [grey(1), grey(4), grey(3)]);
+
+        receiver
+            .handle_packet(Protocol::Wled, &[3, 2, 5, 5, 5, 5], now)
+            .await;
+        assert_eq!(display.flushes(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_restores_background() {
+        let (display, receiver) = receiver();
+        let mut background = receiver.background();
+        let now = Instant::now();
+
+        background.set_xy(0, 0, &grey(1));
+        background.flush().unwrap();
+        assert_eq!(display.pixels(), [grey(1), grey(0), grey(0)]);
+
+        receiver
+            .handle_packet(Protocol::Wled, &[WLED_DRGB, 1, 9, 9, 9, 9, 9, 9], now)
+            .await;
+        // Held back until the realtime frames stop
+        background.set_idx(1, &grey(2));
+        background.flush().unwrap();
+        assert_eq!(display.pixels(), [grey(9), grey(9), grey(0)]);
+
+        receiver.expire(now + TIMEOUT).await;
+        assert_eq!(display.pixels(), [grey(9), grey(9), grey(0)]);
+        receiver.expire(now + Duration::from_secs(1)).await;
+        assert_eq!(display.pixels(), [grey(1), grey(2), grey(0)]);
+        assert_eq!(display.flushes(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_restores_mapped_display() {
+        let strip = SharedDisplay::new(Dimensions {
+            width: 6,
+            height: 1,
+        });
+        let map = Serpentine {
+            dims: Dimensions {
+                width: 3,
+                height: 2,
+            },
+            order: Order::RowMajor,
+        };
+        let mapped = Mapped::new(strip.clone(), map).unwrap();
+        let receiver = RealtimeReceiver::new(Box::new(mapped), TIMEOUT);
+        let mut background = receiver.background();
+        let now = Instant::now();
+
+        background.set_xy(0, 1, &grey(1));
+        background.set_idx(2, &grey(2));
+        // Drawn over the first, which is LED 5
+        background.set_idx(5, &grey(3));
+        background.flush().unwrap();
+        let drawn = [grey(0), grey(0), grey(2), grey(0), grey(0), grey(3)];
+        assert_eq!(strip.pixels(), drawn);
+
+        receiver
+            .handle_packet(Protocol::Wled, &[WLED_DRGB, 1, 9, 9, 9, 9, 9, 9], now)
+            .await;
+        assert_eq!(strip.pixels()[..2], [grey(9); 2]);
+        receiver.expire(now + Duration::from_secs(1)).await;
+        assert_eq!(strip.pixels(), drawn);
+    }
+
+    #[tokio::test]
+    async fn test_wled_without_timeout() {
+        let (display, receiver) = receiver();
+        let now = Instant::now();
+
+        receiver
+            .handle_packet(Protocol::Wled, &[WLED_DRGB, WLED_NO_TIMEOUT, 9, 9, 9], now)
+            .await;
+        receiver.expire(now + Duration::from_secs(3600)).await;
+        assert_eq!(display.pixels()[0], grey(9));
+    }
+
+    #[tokio::test]
+    async fn test_loopback_restores_when_frames_stop() {
+        let (display, receiver) = receiver();
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move { receiver.run(socket, Protocol::Ddp).await });
+
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        sender
+            .send_to(&ddp(DDP_PUSH, 0, &[1; 9]), addr)
+            .await
+            .unwrap();
+
+        display.wait_for_flushes(1).await;
+        assert_eq!(display.pixels(), [grey(1); 3]);
+        display.wait_for_flushes(2).await;
+        assert_eq!(display.pixels(), [grey(0); 3]);
+    }
+}
@@ -0,0 +1,534 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    net::Ipv4Addr,
+    ops::RangeInclusive,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::net::UdpSocket;
+
+use super::{blank, show, DisplayHandle, DmxLayout};
+use crate::{error::Error, pimoroni::Display};
+
+// Streaming ACN (ANSI E1.31) data packets.
+
+pub const PORT: u16 = 5568;
+
+const ACN_PACKET_IDENTIFIER: [u8; 12] = *b"ASC-E1.17\0\0\0";
+const VECTOR_ROOT_E131_DATA: u32 = 0x04;
+const VECTOR_E131_DATA_PACKET: u32 = 0x02;
+const VECTOR_DMP_SET_PROPERTY: u8 = 0x02;
+const NULL_START_CODE: u8 = 0x00;
+const OPTION_PREVIEW_DATA: u8 = 0x80;
+const OPTION_STREAM_TERMINATED: u8 = 0x40;
+const MAX_PACKET_SIZE: usize = 638;
+const MAX_UNIVERSE: u16 = 63999;
+
+/// Multicast group on which a universe is sent.
+pub fn multicast_group(universe: u16) -> Ipv4Addr {
+    let [hi, lo] = universe.to_be_bytes();
+    Ipv4Addr::new(239, 255, hi, lo)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SacnSettings {
+    /// First universe, the display spans as many following universes as
+    /// the layout needs.
+    pub universe: u16,
+    pub layout: DmxLayout,
+    /// Forget a source which has been silent this long, blanking the display
+    /// once none are left.
+    pub source_timeout: Duration,
+}
+
+impl Default for SacnSettings {
+    fn default() -> Self {
+        SacnSettings {
+            universe: 1,
+            layout: DmxLayout::default(),
+            source_timeout: Duration::from_millis(2500),
+        }
+    }
+}
+
+struct DataPacket<'a> {
+    cid: [u8; 16],
+    priority: u8,
+    sequence: u8,
+    options: u8,
+    universe: u16,
+    slots: &'a [u8],
+}
+
+fn parse(packet: &[u8]) -> Option<DataPacket<'_>> {
+    let be32 = |at: usize| u32::from_be_bytes(packet[at..at + 4].try_into().unwrap());
+    let be16 = |at: usize| u16::from_be_bytes([packet[at], packet[at + 1]]);
+
+    if packet.len() < 126
+        || packet[4..16] != ACN_PACKET_IDENTIFIER
+        || be32(18) != VECTOR_ROOT_E131_DATA
+        || be32(40) != VECTOR_E131_DATA_PACKET
+        || packet[117] != VECTOR_DMP_SET_PROPERTY
+        || packet[125] != NULL_START_CODE
+    {
+        return None;
+    }
+
+    // The property values start with the start code
+    let slots = packet.get(126..125 + be16(123) as usize)?;
+
+    Some(DataPacket {
+        cid: packet[22..38].try_into().unwrap(),
+        priority: packet[108],
+        sequence: packet[111],
+        options: packet[112],
+        universe: be16(113),
+        slots,
+    })
+}
+
+struct Source {
+    priority: u8,
+    last_seen: Instant,
+    sequences: HashMap<u16, u8>,
+}
+
+/// Shows the universes sent by the highest priority E1.31 sources, a frame
+/// at a time once every universe the display spans has arrived.
+pub struct SacnReceiver {
+    display: DisplayHandle,
+    settings: SacnSettings,
+    universes: RangeInclusive<u16>,
+    sources: HashMap<[u8; 16], Source>,
+    /// Universes drawn since the display was last flushed.
+    received: BTreeSet<u16>,
+}
+
+impl SacnReceiver {
+    /// Fails if the display would span universes past the last one E1.31
+    /// allows, 63999.
+    pub fn new(display: Box<dyn Display + Send>, settings: SacnSettings) -> Result<Self, Error> {
+        let num_px = display.num_leds();
+        let universes = settings
+            .layout
+            .universes(settings.universe, num_px, MAX_UNIVERSE)?;
+        Ok(SacnReceiver {
+            display: Arc::new(Mutex::new(display)),
+            settings,
+            universes,
+            sources: HashMap::new(),
+            received: BTreeSet::new(),
+        })
+    }
+
+    pub fn universes(&self) -> RangeInclusive<u16> {
+        self.universes.clone()
+    }
+
+    /// Listen on the standard port, joining the multicast group of every
+    /// universe the display spans. Unicast packets are received too.
+    pub async fn bind(&self) -> Result<UdpSocket, Error> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, PORT))
+            .await
+            .map_err(Error::Io)?;
+        for universe in self.universes() {
+            socket
+                .join_multicast_v4(multicast_group(universe), Ipv4Addr::UNSPECIFIED)
+                .map_err(Error::Io)?;
+        }
+        Ok(socket)
+    }
+
+    pub async fn run(&mut self, socket: UdpSocket) -> Result<(), Error> {
+        let mut buf = [0; MAX_PACKET_SIZE];
+        let period = (self.settings.source_timeout / 4).max(Duration::from_millis(1));
+        let mut expiry = tokio::time::interval(period);
+
+        loop {
+            tokio::select! {
+                received = socket.recv(&mut buf) => {
+                    let len = received.map_err(Error::Io)?;
+                    self.handle_packet(&buf[..len], Instant::now()).await;
+                }
+                _ = expiry.tick() => self.expire_sources(Instant::now()).await,
+            }
+        }
+    }
+
+    async fn handle_packet(&mut self, packet: &[u8], now: Instant) {
+        let packet = match parse(packet) {
+            Some(packet) => packet,
+            None => return log::debug!("Ignoring packet which isn't E1.31 data"),
+        };
+        if !self.universes().contains(&packet.universe) || packet.options & OPTION_PREVIEW_DATA != 0
+        {
+            return;
+        }
+
+        if packet.options & OPTION_STREAM_TERMINATED != 0 {
+            if self.sources.remove(&packet.cid).is_some() && self.sources.is_empty() {
+                self.blank().await;
+            }
+            return;
+        }
+
+        let source = self.sources.entry(packet.cid).or_insert_with(|| Source {
+            priority: packet.priority,
+            last_seen: now,
+            sequences: HashMap::new(),
+        });
+        if let Some(&last) = source.sequences.get(&packet.universe) {
+            // Anything up to 20 behind the last packet arrived out of order
+            let diff = packet.sequence.wrapping_sub(last) as i8;
+            if diff <= 0 && diff > -20 {
+                return;
+            }
+        }
+        source.sequences.insert(packet.universe, packet.sequence);
+        source.priority = packet.priority;
+        source.last_seen = now;
+
+        let highest = self.sources.values().map(|s| s.priority).max();
+        if Some(packet.priority) < highest {
+            return;
+        }
+
+        let offset = (packet.universe - self.settings.universe) as usize;
+        self.settings
+            .layout
+            .write(self.display.lock().unwrap().as_mut(), offset, packet.slots);
+
+        // Universes are sent one after another, so wait for the whole frame
+        self.received.insert(packet.universe);
+        if self.universes().all(|u| self.received.contains(&u)) {
+            self.received.clear();
+            if let Err(e) = show(&self.display, |display| display.flush()).await {
+                log::error!("Failed to show E1.31 frame: {}", e);
+            }
+        }
+    }
+
+    async fn expire_sources(&mut self, now: Instant) {
+        let timeout = self.settings.source_timeout;
+        let before = self.sources.len();
+        self.sources
+            .retain(|_, source| now.duration_since(source.last_seen) < timeout);
+
+        if before > 0 && self.sources.is_empty() {
+            log::info!("All E1.31 sources timed out");
+            self.blank().await;
+        }
+    }
+
+    async fn blank(&mut self) {
+        self.received.clear();
+        if let Err(e) = show(&self.display, blank).await {
+            log::error!("Failed to blank display: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rgb::RGB8;
+
+    use super::*;
+    use crate::{net::PixelOrder, pimoroni::Dimensions, testing::SharedDisplay};
+
+    #[derive(Clone)]
+    struct Packet {
+        cid: u8,
+        universe: u16,
+        sequence: u8,
+        priority: u8,
+        options: u8,
+        slots: Vec<u8>,
+    }
+
+    impl Default for Packet {
+        fn default() -> Self {
+            Packet {
+                cid: 1,
+                universe: 5,
+                sequence: 0,
+                priority: 100,
+                options: 0,
+                slots: vec![],
+            }
+        }
+    }
+
+    impl Packet {
+        fn encode(&self) -> Vec<u8> {
+            let mut p = vec![0x00, 0x10, 0x00, 0x00];
+            p.extend(ACN_PACKET_IDENTIFIER);
+            p.extend([0x70, 0x00]);
+            p.extend(VECTOR_ROOT_E131_DATA.to_be_bytes());
+            p.extend([self.cid; 16]);
+            p.extend([0x70, 0x00]);
+            p.extend(VECTOR_E131_DATA_PACKET.to_be_bytes());
+            p.extend([0; 64]);
+            p.push(self.priority);
+            p.extend([0, 0]);
+            p.push(self.sequence);
+            p.push(self.options);
+            p.extend(self.universe.to_be_bytes());
+            p.extend([0x70, 0x00, VECTOR_DMP_SET_PROPERTY, 0xa1, 0, 0, 0, 1]);
+            p.extend((self.slots.len() as u16 + 1).to_be_bytes());
+            p.push(NULL_START_CODE);
+            p.extend(&self.slots);
+            p
+        }
+    }
+
+    fn receiver(settings: SacnSettings) -> (SharedDisplay, SacnReceiver) {
+        let display = SharedDisplay::new(Dimensions {
+            width: 3,
+            height: 1,
+        });
+        let receiver = SacnReceiver::new(Box::new(display.clone()), settings).unwrap();
+        (display, receiver)
+    }
+
+    fn settings() -> SacnSettings {
+        SacnSettings {
+            universe: 5,
+            layout: DmxLayout {
+                start_channel: 1,
+                pixels_per_universe: 3,
+                order: PixelOrder::Rgb,
+            },
+            source_timeout: Duration::from_millis(50),
+        }
+    }
+
+    fn grey(v: u8) -> Vec<u8> {
+        vec![v; 6]
+    }
+
+    #[tokio::test]
+    async fn test_maps_universes_onto_display() {
+        let (display, mut receiver) = receiver(SacnSettings {
+            layout: DmxLayout {
+                start_channel: 2,
+                pixels_per_universe: 2,
+                order: PixelOrder::Bgr,
+            },
+            ..settings()
+        });
+        assert_eq!(receiver.universes(), 5..=6);
+        let now = Instant::now();
+
+        for packet in [
+            Packet {
+                slots: vec![0, 1, 2, 3, 4, 5, 6],
+                ..Default::default()
+            },
+            Packet {
+                universe: 6,
+                slots: vec![0, 7, 8, 9],
+                ..Default::default()
+            },
+            Packet {
+                universe: 7,
+                slots: grey(9),
+                ..Default::default()
+            },
+            Packet {
+                options: OPTION_PREVIEW_DATA,
+                slots: grey(9),
+                ..Default::default()
+            },
+        ] {
+            receiver.handle_packet(&packet.encode(), now).await;
+        }
+
+        assert_eq!(
+            display.pixels(),
+            [RGB8::new(3, 2, 1), RGB8::new(6, 5, 4), RGB8::new(9, 8, 7)]
+        );
+        assert_eq!(display.flushes(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_flushes_once_all_universes_arrive() {
+        let (display, mut receiver) = receiver(SacnSettings {
+            layout: DmxLayout {
+                pixels_per_universe: 2,
+                ..settings().layout
+            },
+            ..settings()
+        });
+        let now = Instant::now();
+        let packet = |universe, sequence, v| {
+            Packet {
+                universe,
+                sequence,
+                slots: grey(v),
+                ..Default::default()
+            }
+            .encode()
+        };
+
+        receiver.handle_packet(&packet(5, 0, 1), now).await;
+        assert_eq!(display.flushes(), 0);
+        receiver.handle_packet(&packet(6, 0, 2), now).await;
+        assert_eq!(display.flushes(), 1);
+        assert_eq!(display.pixels()[2], RGB8::new(2, 2, 2));
+
+        // The same universe again doesn't finish the next frame
+        receiver.handle_packet(&packet(5, 1, 3), now).await;
+        receiver.handle_packet(&packet(5, 2, 4), now).await;
+        assert_eq!(display.flushes(), 1);
+        receiver.handle_packet(&packet(6, 1, 5), now).await;
+        assert_eq!(display.flushes(), 2);
+        assert_eq!(display.pixels()[0], RGB8::new(4, 4, 4));
+    }
+
+    #[tokio::test]
+    async fn test_drops_out_of_order_packets() {
+        let (display, mut receiver) = receiver(settings());
+        let now = Instant::now();
+
+        for (sequence, v) in [(254, 1), (253, 2), (254, 3), (0, 4), (1, 5), (200, 6)] {
+            let packet = Packet {
+                sequence,
+                slots: grey(v),
+                ..Default::default()
+            };
+            receiver.handle_packet(&packet.encode(), now).await;
+        }
+
+        // Sequence numbers wrap, and a big jump is taken as a restart
+        assert_eq!(display.flushes(), 4);
+        assert_eq!(display.pixels()[0], RGB8::new(6, 6, 6));
+    }
+
+    #[tokio::test]
+    async fn test_highest_priority_source_wins() {
+        let (display, mut receiver) = receiver(settings());
+        let now = Instant::now();
+        let high = Packet {
+            cid: 1,
+            priority: 150,
+            slots: grey(1),
+            ..Default::default()
+        };
+        let low = Packet {
+            cid: 2,
+            priority: 100,
+            slots: grey(2),
+            ..Default::default()
+        };
+
+        receiver.handle_packet(&high.encode(), now).await;
+        receiver.handle_packet(&low.encode(), now).await;
+        assert_eq!(display.pixels()[0], RGB8::new(1, 1, 1));
+
+        // Once the higher priority source goes quiet the other takes over
+        let later = now + Duration::from_millis(40);
+        receiver
+            .handle_packet(
+                &Packet {
+                    sequence: 1,
+                    ..low.clone()
+                }
+                .encode(),
+                later,
+            )
+            .await;
+        receiver
+            .expire_sources(now + Duration::from_millis(60))
+            .await;
+        receiver
+            .handle_packet(&Packet { sequence: 2, ..low }.encode(), later)
+            .await;
+        assert_eq!(display.pixels()[0], RGB8::new(2, 2, 2));
+    }
+
+    #[tokio::test]
+    async fn test_stream_terminated_blanks_display() {
+        let (display, mut receiver) = receiver(settings());
+        let now = Instant::now();
+
+        let data = Packet {
+            slots: grey(1),
+            ..Default::default()
+        };
+        receiver.handle_packet(&data.encode(), now).await;
+        let terminated = Packet {
+            sequence: 1,
+            options: OPTION_STREAM_TERMINATED,
+            ..Default::default()
+        };
+        receiver.handle_packet(&terminated.encode(), now).await;
+
+        assert_eq!(display.pixels(), [RGB8::default(); 3]);
+        assert_eq!(display.flushes(), 2);
+    }
+
+    #[test]
+    fn test_rejects_universes_past_the_last() {
+        let display = SharedDisplay::new(Dimensions {
+            width: 3,
+            height: 1,
+        });
+        let new = |universe| {
+            SacnReceiver::new(
+                Box::new(display.clone()),
+                SacnSettings {
+                    universe,
+                    layout: DmxLayout {
+                        pixels_per_universe: 2,
+                        ..settings().layout
+                    },
+                    ..settings()
+                },
+            )
+        };
+
+        assert_eq!(new(63998).unwrap().universes(), 63998..=63999);
+        assert!(matches!(
+            new(63999),
+            Err(Error::UniverseOutOfRange { count: 2, .. })
+        ));
+        assert!(new(u16::MAX).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ignores_malformed_packets() {
+        let (display, mut receiver) = receiver(settings());
+        let mut packet = Packet {
+            slots: grey(1),
+            ..Default::default()
+        }
+        .encode();
+
+        receiver.handle_packet(&packet[..125], Instant::now()).await;
+        packet[124] += 1;
+        receiver.handle_packet(&packet, Instant::now()).await;
+
+        assert_eq!(display.flushes(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_loopback_blanks_when_stream_stops() {
+        let (display, mut receiver) = receiver(settings());
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move { receiver.run(socket).await });
+
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let packet = Packet {
+            slots: grey(1),
+            ..Default::default()
+        };
+        sender.send_to(&packet.encode(), addr).await.unwrap();
+
+        display.wait_for_flushes(1).await;
+        assert_eq!(display.pixels()[1], RGB8::new(1, 1, 1));
+        display.wait_for_flushes(2).await;
+        assert_eq!(display.pixels(), [RGB8::default(); 3]);
+    }
+}
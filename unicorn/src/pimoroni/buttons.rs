@@ -0,0 +1,878 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Weak,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use rppal::gpio::{Gpio, InputPin, Level, Trigger};
+use tokio::sync::{broadcast, watch};
+
+use crate::error::Error;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Button {
+    A,
+    B,
+    X,
+    Y,
+    /// An extra button between this GPIO pin (BCM numbering) and ground.
+    Gpio(u8),
+}
+impl Button {
+    /// The Mini's own buttons.
+    pub const ALL: [Button; 4] = [Button::A, Button::B, Button::X, Button::Y];
+
+    pub fn pin(&self) -> u8 {
+        match self {
+            Button::A => 5,
+            Button::B => 6,
+            Button::X => 16,
+            Button::Y => 24,
+            Button::Gpio(pin) => *pin,
+        }
+    }
+}
+
+/// A quadrature rotary encoder, with its common pin to ground.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Encoder {
+    pub a: u8,
+    pub b: u8,
+    /// Quadrature steps between detents, usually 4 but sometimes 2 or 1.
+    pub steps_per_detent: u8,
+}
+
+impl Encoder {
+    pub fn new(a: u8, b: u8) -> Self {
+        Encoder {
+            a,
+            b,
+            steps_per_detent: 4,
+        }
+    }
+}
+
+/// Which GPIO pins have buttons and encoders on them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InputMap {
+    pub buttons: Vec<Button>,
+    pub encoders: Vec<Encoder>,
+}
+
+impl Default for InputMap {
+    /// Just the Mini's own buttons.
+    fn default() -> Self {
+        InputMap {
+            buttons: Button::ALL.to_vec(),
+            encoders: vec![],
+        }
+    }
+}
+
+impl InputMap {
+    /// Every pin to watch, with the encoders' pins as plain buttons.
+    fn pins(&self) -> Vec<Button> {
+        let encoder_pins = self
+            .encoders
+            .iter()
+            .flat_map(|e| [Button::Gpio(e.a), Button::Gpio(e.b)]);
+        self.buttons.iter().copied().chain(encoder_pins).collect()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    Pressed,
+    Released,
+    /// Still held `long_press` after being pressed.
+    LongPress,
+    /// Pressed again within `double_click` of a short press being released.
+    /// Follows the second `Pressed`.
+    DoubleClick,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ButtonEvent {
+    pub button: Button,
+    pub kind: EventKind,
+    pub timestamp: Instant,
+}
+
+/// An encoder turned by a detent.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EncoderEvent {
+    pub encoder: Encoder,
+    /// 1 when pin A leads, which is clockwise for most encoders, otherwise -1.
+    pub steps: i32,
+    /// Detents per second, judged from the last one, or 0.0 if the encoder
+    /// was still for a while before this.
+    pub velocity: f32,
+    pub timestamp: Instant,
+}
+
+/// Anything from the buttons and encoders, in the order it happened.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InputEvent {
+    Button(ButtonEvent),
+    Encoder(EncoderEvent),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ButtonSettings {
+    /// Edges on a pin this soon after the last one it accepted are contact
+    /// bounce, and ignored.
+    pub debounce: Duration,
+    pub long_press: Duration,
+    pub double_click: Duration,
+}
+
+impl Default for ButtonSettings {
+    fn default() -> Self {
+        ButtonSettings {
+            debounce: Duration::from_millis(30),
+            long_press: Duration::from_millis(800),
+            double_click: Duration::from_millis(300),
+        }
+    }
+}
+
+#[derive(Default)]
+struct PinState {
+    pressed: bool,
+    last_edge: Option<Instant>,
+    /// Where the pin was last seen while bouncing, to settle on once the
+    /// debounce window is over.
+    pending: Option<bool>,
+    pressed_at: Option<Instant>,
+    long_pressed: bool,
+    double_clicked: bool,
+    /// When the last short press was released, while a double click is possible.
+    clicked_at: Option<Instant>,
+}
+
+impl PinState {
+    /// When the debounce window ends, if an edge inside it is waiting.
+    fn settles_at(&self, debounce: Duration) -> Option<Instant> {
+        self.pending.and(self.last_edge).map(|last| last + debounce)
+    }
+
+    /// Accept the level seen while bouncing, if the window is over by `now`.
+    fn settle(
+        &mut self,
+        button: Button,
+        settings: ButtonSettings,
+        now: Instant,
+    ) -> Vec<ButtonEvent> {
+        match self.settles_at(settings.debounce) {
+            Some(at) if at <= now => {
+                let pressed = self.pending.take().unwrap_or(self.pressed);
+                if pressed == self.pressed {
+                    vec![]
+                } else {
+                    self.accept(button, pressed, at, settings)
+                }
+            }
+            _ => vec![],
+        }
+    }
+
+    fn accept(
+        &mut self,
+        button: Button,
+        pressed: bool,
+        at: Instant,
+        settings: ButtonSettings,
+    ) -> Vec<ButtonEvent> {
+        self.last_edge = Some(at);
+        self.pressed = pressed;
+
+        let event = |kind| ButtonEvent {
+            button,
+            kind,
+            timestamp: at,
+        };
+        if pressed {
+            self.pressed_at = Some(at);
+            self.long_pressed = false;
+            let double = self.clicked_at.take().is_some_and(|clicked| {
+                at.saturating_duration_since(clicked) <= settings.double_click
+            });
+            self.double_clicked = double;
+
+            if double {
+                vec![event(EventKind::Pressed), event(EventKind::DoubleClick)]
+            } else {
+                vec![event(EventKind::Pressed)]
+            }
+        } else {
+            // Only a short press can be the first half of a double click,
+            // and the second half can't start a new one
+            self.clicked_at = (!self.long_pressed && !self.double_clicked).then_some(at);
+            self.pressed_at = None;
+            vec![event(EventKind::Released)]
+        }
+    }
+}
+
+/// Turns the raw edges of the buttons into events.
+pub(crate) struct ButtonDecoder {
+    settings: ButtonSettings,
+    pins: BTreeMap<Button, PinState>,
+}
+
+impl ButtonDecoder {
+    pub fn new(settings: ButtonSettings) -> Self {
+        ButtonDecoder {
+            settings,
+            pins: Default::default(),
+        }
+    }
+
+    /// The button was seen going down (`pressed`) or up at `at`.
+    pub fn edge(&mut self, button: Button, pressed: bool, at: Instant) -> Vec<ButtonEvent> {
+        let settings = self.settings;
+        let pin = self.pins.entry(button).or_default();
+        let bouncing = pin
+            .last_edge
+            .is_some_and(|last| at.saturating_duration_since(last) < settings.debounce);
+        if bouncing {
+            pin.pending = Some(pressed);
+            return vec![];
+        }
+
+        // A change left over from the last window happened before this one
+        let mut events = pin.settle(button, settings, at);
+        pin.pending = None;
+        if pressed != pin.pressed {
+            events.extend(pin.accept(button, pressed, at, settings));
+        }
+        events
+    }
+
+    /// Events which fall due by `now` without any edges, i.e. changes which
+    /// settled inside the debounce window and long presses.
+    pub fn tick(&mut self, now: Instant) -> Vec<ButtonEvent> {
+        let settings = self.settings;
+        let mut events = vec![];
+        for (&button, pin) in &mut self.pins {
+            events.extend(pin.settle(button, settings, now));
+
+            let due = pin
+                .pressed_at
+                .is_some_and(|at| now.saturating_duration_since(at) >= settings.long_press);
+            if due && !pin.long_pressed {
+                pin.long_pressed = true;
+                events.push(ButtonEvent {
+                    button,
+                    kind: EventKind::LongPress,
+                    timestamp: now,
+                });
+            }
+        }
+        events
+    }
+
+    /// When `tick` next needs calling, if a button is being held or bouncing.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let settings = self.settings;
+        let long_presses = self
+            .pins
+            .values()
+            .filter(|pin| !pin.long_pressed)
+            .filter_map(|pin| pin.pressed_at)
+            .map(|at| at + settings.long_press);
+        let settles = self
+            .pins
+            .values()
+            .filter_map(|pin| pin.settles_at(settings.debounce));
+        long_presses.chain(settles).min()
+    }
+}
+
+/// How long an encoder must be still for its velocity to start from zero.
+const VELOCITY_WINDOW: Duration = Duration::from_secs(1);
+
+/// Quadrature step for each (previous << 2 | current) state of pins A and B,
+/// positive when A leads. Impossible jumps of two states, from a missed edge
+/// or contact bounce, count for nothing.
+const QUADRATURE: [i8; 16] = [0, -1, 1, 0, 1, 0, 0, -1, -1, 0, 0, 1, 0, 1, -1, 0];
+
+#[derive(Default)]
+struct EncoderState {
+    a: bool,
+    b: bool,
+    /// Steps since the last detent, wide enough for any `steps_per_detent`.
+    count: i16,
+    last_detent: Option<Instant>,
+}
+
+/// Turns the edges on encoders' pins into events.
+pub(crate) struct EncoderDecoder {
+    encoders: BTreeMap<Encoder, EncoderState>,
+}
+
+impl EncoderDecoder {
+    pub fn new(encoders: &[Encoder]) -> Self {
+        EncoderDecoder {
+            encoders: encoders
+                .iter()
+                .map(|e| (*e, EncoderState::default()))
+                .collect(),
+        }
+    }
+
+    /// Whether `pin` belongs to an encoder.
+    pub fn owns(&self, pin: u8) -> bool {
+        self.encoders.keys().any(|e| e.a == pin || e.b == pin)
+    }
+
+    /// `pin` was seen going low (`low`) or high at `at`.
+    pub fn edge(&mut self, pin: u8, low: bool, at: Instant) -> Option<EncoderEvent> {
+        let (&encoder, state) = self
+            .encoders
+            .iter_mut()
+            .find(|(e, _)| e.a == pin || e.b == pin)?;
+
+        let previous = (state.a as usize) << 1 | state.b as usize;
+        if encoder.a == pin {
+            state.a = low;
+        } else {
+            state.b = low;
+        }
+        let current = (state.a as usize) << 1 | state.b as usize;
+        state.count += QUADRATURE[previous << 2 | current] as i16;
+
+        let per_detent = encoder.steps_per_detent.max(1) as i16;
+        if state.count.abs() < per_detent {
+            return None;
+        }
+        let steps = state.count.signum();
+        state.count -= steps * per_detent;
+
+        let velocity = state
+            .last_detent
+            .map(|last| at.saturating_duration_since(last))
+            .filter(|since| *since < VELOCITY_WINDOW && !since.is_zero())
+            .map_or(0.0, |since| 1.0 / since.as_secs_f32());
+        state.last_detent = Some(at);
+
+        Some(EncoderEvent {
+            encoder,
+            steps: steps as i32,
+            velocity,
+            timestamp: at,
+        })
+    }
+}
+
+/// A button going down or up.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Edge {
+    pub button: Button,
+    pub pressed: bool,
+    pub at: Instant,
+}
+
+/// Events queued for each subscriber before the oldest are dropped.
+const EVENT_CAPACITY: usize = 32;
+/// Longest the GPIO is polled for before checking whether to stop.
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Where the raw button edges come from, normally the Pi's GPIO pins.
+pub trait ButtonInput: Send {
+    /// Block until the next edge, returning `None` if there was none by
+    /// `deadline`. May give up early, so the watch can check if it's stopped.
+    fn next_edge(&mut self, deadline: Option<Instant>) -> Result<Option<Edge>, Error>;
+
+    /// The clock edges are timestamped with.
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// The buttons wired to GPIO pins on the Pi. Encoders' pins are reported
+/// as [`Button::Gpio`] edges.
+pub struct GpioButtons {
+    gpio: Gpio,
+    pins: Vec<InputPin>,
+    buttons: Vec<Button>,
+    first_poll: bool,
+}
+
+impl GpioButtons {
+    /// The Mini's own buttons.
+    pub fn new() -> Result<Self, Error> {
+        Self::with_map(&InputMap::default())
+    }
+
+    /// Every button and encoder in `map`.
+    pub fn with_map(map: &InputMap) -> Result<Self, Error> {
+        let gpio = Gpio::new()?;
+
+        fn get_pin(gpio: &Gpio, id: u8) -> Result<InputPin, Error> {
+            let mut pin = gpio.get(id)?.into_input_pullup();
+            pin.set_interrupt(Trigger::Both)?;
+            Ok(pin)
+        }
+
+        let buttons = map.pins();
+        let pins = buttons
+            .iter()
+            .map(|button| get_pin(&gpio, button.pin()))
+            .collect::<Result<_, _>>()?;
+
+        Ok(GpioButtons {
+            gpio,
+            pins,
+            buttons,
+            first_poll: true,
+        })
+    }
+}
+
+impl ButtonInput for GpioButtons {
+    fn next_edge(&mut self, deadline: Option<Instant>) -> Result<Option<Edge>, Error> {
+        let pins = self.pins.iter().collect::<Vec<_>>();
+        let timeout = deadline
+            .map(|at| at.saturating_duration_since(Instant::now()))
+            .map_or(STOP_CHECK_INTERVAL, |timeout| {
+                timeout.min(STOP_CHECK_INTERVAL)
+            });
+        // Interrupts from before the first poll are stale
+        let result = self
+            .gpio
+            .poll_interrupts(&pins, self.first_poll, Some(timeout))?;
+        self.first_poll = false;
+
+        Ok(result.map(|(pin, level)| {
+            let i = pins.iter().position(|p| *p == pin).unwrap();
+            Edge {
+                button: self.buttons[i],
+                // The buttons pull the pins low
+                pressed: level == Level::Low,
+                at: Instant::now(),
+            }
+        }))
+    }
+}
+
+/// Replays a fixed list of edges for testing, on a clock which jumps ahead
+/// rather than waiting for them. Stops with an error once they run out.
+#[derive(Clone, Debug)]
+pub struct ScriptedButtons {
+    start: Instant,
+    now: Instant,
+    edges: VecDeque<Edge>,
+}
+
+impl ScriptedButtons {
+    pub fn new() -> Self {
+        let start = Instant::now();
+        ScriptedButtons {
+            start,
+            now: start,
+            edges: VecDeque::new(),
+        }
+    }
+
+    /// Add an edge `after` the start, later than any already added.
+    pub fn push(&mut self, after: Duration, button: Button, pressed: bool) {
+        let at = self.start + after;
+        assert!(
+            self.edges.back().map_or(true, |last| last.at <= at),
+            "Edges must be added in order"
+        );
+        self.edges.push_back(Edge {
+            button,
+            pressed,
+            at,
+        });
+    }
+
+    pub fn start(&self) -> Instant {
+        self.start
+    }
+}
+
+impl Default for ScriptedButtons {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ButtonInput for ScriptedButtons {
+    fn next_edge(&mut self, deadline: Option<Instant>) -> Result<Option<Edge>, Error> {
+        match (self.edges.front(), deadline) {
+            (Some(edge), Some(deadline)) if edge.at > deadline => {
+                self.now = deadline;
+                Ok(None)
+            }
+            (Some(edge), _) => {
+                self.now = edge.at;
+                Ok(self.edges.pop_front())
+            }
+            (None, Some(deadline)) => {
+                self.now = deadline;
+                Ok(None)
+            }
+            (None, None) => Err(Error::Io(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "No more scripted button edges",
+            ))),
+        }
+    }
+
+    fn now(&self) -> Instant {
+        self.now
+    }
+}
+
+/// Stops the watch thread once every handle on it has gone.
+pub(crate) struct Shared {
+    stop: Arc<AtomicBool>,
+    /// Kept only to subscribe new handles to the events.
+    events: broadcast::Receiver<InputEvent>,
+    pressed: watch::Receiver<Option<Button>>,
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Handle on a thread watching the buttons, which is stopped once it and
+/// all its clones are dropped.
+pub struct Buttons {
+    shared: Arc<Shared>,
+    events: broadcast::Receiver<InputEvent>,
+}
+
+impl Buttons {
+    /// Decode edges from `input` on a new thread, those on `encoders`' pins
+    /// as turns of the encoders.
+    pub(crate) fn start(
+        mut input: Box<dyn ButtonInput>,
+        settings: ButtonSettings,
+        encoders: &[Encoder],
+    ) -> Result<Self, Error> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (pressed_tx, pressed) = watch::channel(None);
+        let (events_tx, events) = broadcast::channel(EVENT_CAPACITY);
+
+        let stopping = stop.clone();
+        let encoders = encoders.to_vec();
+        thread::Builder::new()
+            .name("unicorn-buttons".to_string())
+            .spawn(move || {
+                let mut decoder = ButtonDecoder::new(settings);
+                let mut encoders = EncoderDecoder::new(&encoders);
+
+                while !stopping.load(Ordering::Relaxed) {
+                    // Wake up in time to report a long press, if a button is held
+                    let decoded = match input.next_edge(decoder.next_deadline()) {
+                        Ok(Some(edge)) if encoders.owns(edge.button.pin()) => {
+                            let turned = encoders.edge(edge.button.pin(), edge.pressed, edge.at);
+                            if let Some(event) = turned {
+                                events_tx.send(InputEvent::Encoder(event)).ok();
+                            }
+                            continue;
+                        }
+                        Ok(Some(edge)) => decoder.edge(edge.button, edge.pressed, edge.at),
+                        Ok(None) => decoder.tick(input.now()),
+                        Err(e) => {
+                            log::error!("Stopped watching buttons: {}", e);
+                            return;
+                        }
+                    };
+
+                    for event in decoded {
+                        if event.kind == EventKind::Pressed {
+                            pressed_tx.send_replace(Some(event.button));
+                        }
+                        // Nobody may be listening for events
+                        events_tx.send(InputEvent::Button(event)).ok();
+                    }
+                }
+            })
+            .map_err(Error::Io)?;
+
+        Ok(Buttons {
+            shared: Arc::new(Shared {
+                stop,
+                events: events.resubscribe(),
+                pressed,
+            }),
+            events,
+        })
+    }
+
+    /// Another handle on the watch, if it's still running.
+    pub(crate) fn from_shared(shared: &Weak<Shared>) -> Option<Self> {
+        let shared = shared.upgrade()?;
+        let events = shared.events.resubscribe();
+        Some(Buttons { shared, events })
+    }
+
+    pub(crate) fn downgrade(&self) -> Weak<Shared> {
+        Arc::downgrade(&self.shared)
+    }
+
+    /// Wait for the next button event, skipping any from encoders, or
+    /// `None` once the watch has failed.
+    pub async fn next(&mut self) -> Option<ButtonEvent> {
+        loop {
+            if let InputEvent::Button(event) = self.next_input().await? {
+                return Some(event);
+            }
+        }
+    }
+
+    /// Wait for the next event from a button or encoder, or `None` once the
+    /// watch has failed. Events are dropped, oldest first, if they aren't
+    /// taken quickly enough.
+    pub async fn next_input(&mut self) -> Option<InputEvent> {
+        loop {
+            match self.events.recv().await {
+                Ok(event) => return Some(event),
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    log::warn!("Dropped {} button events", n)
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// The last button pressed.
+    pub fn last_pressed(&self) -> watch::Receiver<Option<Button>> {
+        self.shared.pressed.clone()
+    }
+}
+
+impl Clone for Buttons {
+    fn clone(&self) -> Self {
+        Buttons {
+            shared: self.shared.clone(),
+            events: self.events.resubscribe(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    fn kinds(events: Vec<ButtonEvent>) -> Vec<(Button, EventKind)> {
+        events.into_iter().map(|e| (e.button, e.kind)).collect()
+    }
+
+    #[test]
+    fn test_press_and_release() {
+        let mut decoder = ButtonDecoder::new(ButtonSettings::default());
+        let t0 = Instant::now();
+
+        let events = decoder.edge(Button::A, true, t0);
+        assert_eq!(events[0].timestamp, t0);
+        assert_eq!(kinds(events), [(Button::A, EventKind::Pressed)]);
+        assert_eq!(
+            kinds(decoder.edge(Button::A, false, t0 + ms(100))),
+            [(Button::A, EventKind::Released)]
+        );
+    }
+
+    #[test]
+    fn test_debounce_is_per_pin() {
+        let mut decoder = ButtonDecoder::new(ButtonSettings::default());
+        let t0 = Instant::now();
+
+        decoder.edge(Button::A, true, t0);
+        assert!(decoder.edge(Button::A, false, t0 + ms(5)).is_empty());
+        assert!(decoder.edge(Button::A, true, t0 + ms(10)).is_empty());
+        // Another button pressed straight away still counts
+        assert_eq!(
+            kinds(decoder.edge(Button::B, true, t0 + ms(10))),
+            [(Button::B, EventKind::Pressed)]
+        );
+        assert_eq!(
+            kinds(decoder.edge(Button::A, false, t0 + ms(50))),
+            [(Button::A, EventKind::Released)]
+        );
+    }
+
+    #[test]
+    fn test_tap_inside_debounce_window() {
+        let mut decoder = ButtonDecoder::new(ButtonSettings::default());
+        let t0 = Instant::now();
+
+        decoder.edge(Button::A, true, t0);
+        assert!(decoder.edge(Button::A, false, t0 + ms(20)).is_empty());
+        assert_eq!(decoder.next_deadline(), Some(t0 + ms(30)));
+        assert!(decoder.tick(t0 + ms(29)).is_empty());
+        let events = decoder.tick(t0 + ms(30));
+        assert_eq!(events[0].timestamp, t0 + ms(30));
+        assert_eq!(kinds(events), [(Button::A, EventKind::Released)]);
+        // No long press from a button that's no longer held
+        assert_eq!(decoder.next_deadline(), None);
+        assert!(decoder.tick(t0 + ms(800)).is_empty());
+
+        // A release still pending when the next edge comes in goes first
+        decoder.edge(Button::B, true, t0);
+        decoder.edge(Button::B, false, t0 + ms(10));
+        assert_eq!(
+            kinds(decoder.edge(Button::B, true, t0 + ms(500))),
+            [
+                (Button::B, EventKind::Released),
+                (Button::B, EventKind::Pressed)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_long_press() {
+        let mut decoder = ButtonDecoder::new(ButtonSettings::default());
+        let t0 = Instant::now();
+
+        decoder.edge(Button::X, true, t0);
+        assert_eq!(decoder.next_deadline(), Some(t0 + ms(800)));
+        assert!(decoder.tick(t0 + ms(799)).is_empty());
+        assert_eq!(
+            kinds(decoder.tick(t0 + ms(800))),
+            [(Button::X, EventKind::LongPress)]
+        );
+        assert!(decoder.tick(t0 + ms(900)).is_empty());
+        assert_eq!(decoder.next_deadline(), None);
+
+        // A long press can't start a double click
+        decoder.edge(Button::X, false, t0 + ms(1000));
+        assert_eq!(
+            kinds(decoder.edge(Button::X, true, t0 + ms(1100))),
+            [(Button::X, EventKind::Pressed)]
+        );
+    }
+
+    #[test]
+    fn test_scripted_buttons() {
+        let mut script = ScriptedButtons::new();
+        script.push(ms(10), Button::A, true);
+        script.push(ms(20), Button::A, false);
+        let t0 = script.start();
+
+        assert_eq!(script.next_edge(Some(t0 + ms(5))).unwrap(), None);
+        let edge = script.next_edge(None).unwrap().unwrap();
+        assert_eq!(
+            (edge.button, edge.pressed, edge.at),
+            (Button::A, true, t0 + ms(10))
+        );
+        assert!(script.next_edge(Some(t0 + ms(20))).unwrap().is_some());
+        assert!(script.next_edge(None).is_err());
+    }
+
+    #[test]
+    fn test_extra_buttons() {
+        let mut decoder = ButtonDecoder::new(ButtonSettings::default());
+        let t0 = Instant::now();
+
+        assert_eq!(Button::Gpio(17).pin(), 17);
+        decoder.edge(Button::Gpio(17), true, t0);
+        assert_eq!(
+            kinds(decoder.tick(t0 + ms(800))),
+            [(Button::Gpio(17), EventKind::LongPress)]
+        );
+
+        let map = InputMap {
+            encoders: vec![Encoder::new(22, 23)],
+            ..Default::default()
+        };
+        assert_eq!(
+            map.pins(),
+            [
+                Button::A,
+                Button::B,
+                Button::X,
+                Button::Y,
+                Button::Gpio(22),
+                Button::Gpio(23)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encoder() {
+        let encoder = Encoder::new(22, 23);
+        let mut decoder = EncoderDecoder::new(&[encoder]);
+        let t0 = Instant::now();
+        assert!(decoder.owns(23) && !decoder.owns(5));
+
+        // A leads B through a full cycle, a detent
+        assert!(decoder.edge(22, true, t0).is_none());
+        assert!(decoder.edge(23, true, t0 + ms(1)).is_none());
+        assert!(decoder.edge(22, false, t0 + ms(2)).is_none());
+        let event = decoder.edge(23, false, t0 + ms(3)).unwrap();
+        assert_eq!((event.encoder, event.steps), (encoder, 1));
+        assert_eq!(event.velocity, 0.0);
+
+        // Bounce back and forth on one pin counts for nothing
+        decoder.edge(23, true, t0 + ms(50));
+        decoder.edge(23, false, t0 + ms(51));
+
+        // B leads A a quarter of a second later
+        decoder.edge(23, true, t0 + ms(100));
+        decoder.edge(22, true, t0 + ms(150));
+        decoder.edge(23, false, t0 + ms(200));
+        let event = decoder.edge(22, false, t0 + ms(253)).unwrap();
+        assert_eq!(event.steps, -1);
+        assert!((event.velocity - 4.0).abs() < 0.001);
+
+        assert!(decoder.edge(5, true, t0).is_none());
+    }
+
+    #[test]
+    fn test_encoder_with_many_steps_per_detent() {
+        let encoder = Encoder {
+            steps_per_detent: 200,
+            ..Encoder::new(22, 23)
+        };
+        let mut decoder = EncoderDecoder::new(&[encoder]);
+        let t0 = Instant::now();
+
+        let mut events = vec![];
+        for cycle in 0..50 {
+            for (pin, low) in [(22, true), (23, true), (22, false), (23, false)] {
+                events.extend(decoder.edge(pin, low, t0 + ms(cycle)));
+            }
+        }
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].steps, 1);
+    }
+
+    #[test]
+    fn test_double_click() {
+        let mut decoder = ButtonDecoder::new(ButtonSettings::default());
+        let t0 = Instant::now();
+
+        decoder.edge(Button::Y, true, t0);
+        decoder.edge(Button::Y, false, t0 + ms(100));
+        assert_eq!(
+            kinds(decoder.edge(Button::Y, true, t0 + ms(300))),
+            [
+                (Button::Y, EventKind::Pressed),
+                (Button::Y, EventKind::DoubleClick)
+            ]
+        );
+
+        // A third click starts afresh, as does one after the threshold
+        decoder.edge(Button::Y, false, t0 + ms(400));
+        assert_eq!(decoder.edge(Button::Y, true, t0 + ms(500)).len(), 1);
+        decoder.edge(Button::Y, false, t0 + ms(600));
+        assert_eq!(decoder.edge(Button::Y, true, t0 + ms(1000)).len(), 1);
+    }
+}
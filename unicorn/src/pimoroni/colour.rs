@@ -0,0 +1,82 @@
+use rgb::RGB8;
+
+/// Colour corrections applied to every LED as a frame is flushed, so callers
+/// can work in linear colour and get similar results on both boards.
+///
+/// Each channel is gamma corrected, then scaled by its white balance factor,
+/// then clamped to `max_brightness`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColourPipeline {
+    gamma_lut: [u8; 256],
+    white_balance: [f32; 3],
+    max_brightness: u8,
+}
+
+impl ColourPipeline {
+    pub fn new(gamma: f32, white_balance: [f32; 3], max_brightness: u8) -> Self {
+        assert!(gamma > 0.0, "Gamma must be positive: {}", gamma);
+        let mut gamma_lut = [0; 256];
+        for (i, v) in gamma_lut.iter_mut().enumerate() {
+            *v = ((i as f32 / 255.0).powf(gamma) * 255.0).round() as u8;
+        }
+
+        ColourPipeline {
+            gamma_lut,
+            white_balance,
+            max_brightness,
+        }
+    }
+
+    /// Leaves every colour untouched.
+    pub fn identity() -> Self {
+        Self::new(1.0, [1.0; 3], u8::MAX)
+    }
+
+    pub fn apply(&self, rgb: &RGB8) -> RGB8 {
+        let channel = |v: u8, balance: f32| {
+            let v = self.gamma_lut[v as usize] as f32 * balance;
+            (v.round().clamp(0.0, 255.0) as u8).min(self.max_brightness)
+        };
+
+        RGB8::new(
+            channel(rgb.r, self.white_balance[0]),
+            channel(rgb.g, self.white_balance[1]),
+            channel(rgb.b, self.white_balance[2]),
+        )
+    }
+}
+
+impl Default for ColourPipeline {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity() {
+        let pipeline = ColourPipeline::identity();
+        for v in 0..=255 {
+            assert_eq!(pipeline.apply(&RGB8::new(v, v, v)), RGB8::new(v, v, v));
+        }
+    }
+
+    #[test]
+    fn test_gamma_white_balance_and_clamp() {
+        let pipeline = ColourPipeline::new(2.0, [1.0, 0.5, 2.0], 200);
+
+        assert_eq!(pipeline.apply(&RGB8::new(0, 0, 0)), RGB8::new(0, 0, 0));
+        // 128 -> 64 after gamma, then balanced per channel
+        assert_eq!(
+            pipeline.apply(&RGB8::new(128, 128, 128)),
+            RGB8::new(64, 32, 128)
+        );
+        assert_eq!(
+            pipeline.apply(&RGB8::new(255, 255, 255)),
+            RGB8::new(200, 128, 200)
+        );
+    }
+}
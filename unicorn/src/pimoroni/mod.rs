@@ -1,14 +1,32 @@
 use rgb::RGB8;
 
+use crate::error::Error;
+
+pub mod buttons;
+pub mod colour;
+pub mod pixelmap;
+pub mod transform;
+pub mod transport;
 pub mod unicorn;
 pub mod unicornmini;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Dimensions {
     pub width: usize,
     pub height: usize,
 }
 impl Dimensions {
+    /// Geometry of the Unicorn HAT HD.
+    pub const UNICORN: Dimensions = Dimensions {
+        width: 16,
+        height: 16,
+    };
+    /// Geometry of the Unicorn HAT Mini.
+    pub const UNICORN_MINI: Dimensions = Dimensions {
+        width: 17,
+        height: 7,
+    };
+
     pub fn num_px(&self) -> usize {
         self.width * self.height
     }
@@ -17,7 +35,54 @@ impl Dimensions {
 pub trait Display {
     fn set_xy(&mut self, x: usize, y: usize, rgb: &RGB8);
     fn set_idx(&mut self, idx: usize, rgb: &RGB8);
-    fn flush(&mut self);
-    fn reset(&mut self);
+    fn flush(&mut self) -> Result<(), Error>;
+    fn reset(&mut self) -> Result<(), Error>;
     fn dimensions(&self) -> &Dimensions;
+
+    /// How many LEDs [`Display::set_idx`] can address. The same as the
+    /// number of pixels unless a pixel map leaves gaps or LEDs off the grid.
+    fn num_leds(&self) -> usize {
+        self.dimensions().num_px()
+    }
+    /// Scale the whole display between off (0.0) and full brightness (1.0),
+    /// taking effect on the next flush. Out-of-range values are clamped.
+    /// Displays without brightness control ignore it.
+    ///
+    /// Boards start at different levels: the Unicorn HD at full brightness,
+    /// the Unicorn Mini at the lowest level its controller has, 1/63. Use
+    /// [`Display::brightness`] to find the level before changing it.
+    fn set_brightness(&mut self, _brightness: f32) {}
+
+    /// The current brightness, between 0.0 and 1.0. Always 1.0 for displays
+    /// without brightness control.
+    fn brightness(&self) -> f32 {
+        1.0
+    }
+
+    /// As [`Display::set_xy`], but returns an error instead of panicking when
+    /// the coordinate is off the display.
+    fn try_set_xy(&mut self, x: usize, y: usize, rgb: &RGB8) -> Result<(), Error> {
+        let Dimensions { width, height } = *self.dimensions();
+        if x >= width || y >= height {
+            return Err(Error::CoordinateOutOfRange {
+                x,
+                y,
+                width,
+                height,
+            });
+        }
+        self.set_xy(x, y, rgb);
+        Ok(())
+    }
+
+    /// As [`Display::set_idx`], but returns an error instead of panicking when
+    /// the index is off the display.
+    fn try_set_idx(&mut self, idx: usize, rgb: &RGB8) -> Result<(), Error> {
+        let num_px = self.num_leds();
+        if idx >= num_px {
+            return Err(Error::IndexOutOfRange { idx, num_px });
+        }
+        self.set_idx(idx, rgb);
+        Ok(())
+    }
 }
@@ -0,0 +1,564 @@
+use std::{collections::BTreeMap, fs, io::BufRead, path::Path};
+
+use rgb::RGB8;
+use serde_json::Value;
+
+use super::{Dimensions, Display};
+use crate::error::Error;
+
+/// Where each LED of a display is, relating `(x, y)` coordinates to the
+/// indices the hardware is driven by, i.e. those of [`Display::set_idx`].
+pub trait PixelMap: Send + Sync {
+    fn dimensions(&self) -> Dimensions;
+
+    /// Index of the LED at `(x, y)`, or `None` if there isn't one there.
+    fn index(&self, x: usize, y: usize) -> Option<usize>;
+
+    /// Where the LED with `index` is, or `None` if there isn't one.
+    fn position(&self, index: usize) -> Option<(usize, usize)>;
+}
+
+/// Check every LED `map` places is one of the `num_leds` a display has.
+pub(crate) fn check_fits(map: &dyn PixelMap, num_leds: usize) -> Result<(), Error> {
+    let Dimensions { width, height } = map.dimensions();
+    for y in 0..height {
+        for x in 0..width {
+            match map.index(x, y) {
+                Some(idx) if idx >= num_leds => {
+                    return Err(Error::InvalidPixelMap(format!(
+                        "LED {} at ({}, {}) is beyond the display's {} LEDs",
+                        idx, x, y, num_leds
+                    )))
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Which way the indices run first.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Order {
+    /// Along each row, top to bottom, like the Unicorn HD.
+    RowMajor,
+    /// Down each column, left to right, like the Unicorn Mini.
+    ColumnMajor,
+}
+
+/// LEDs in a full grid, every row or column starting from the same side.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Grid {
+    pub dims: Dimensions,
+    pub order: Order,
+}
+
+impl Grid {
+    pub const UNICORN: Grid = Grid {
+        dims: Dimensions::UNICORN,
+        order: Order::RowMajor,
+    };
+    pub const UNICORN_MINI: Grid = Grid {
+        dims: Dimensions::UNICORN_MINI,
+        order: Order::ColumnMajor,
+    };
+
+    /// The map of the board with the same geometry: column-major for the
+    /// 17x7 Unicorn Mini, row-major (like the Unicorn HD) for anything else.
+    pub fn for_dimensions(dims: Dimensions) -> Self {
+        if dims == Dimensions::UNICORN_MINI {
+            Grid::UNICORN_MINI
+        } else {
+            Grid {
+                dims,
+                order: Order::RowMajor,
+            }
+        }
+    }
+}
+
+impl PixelMap for Grid {
+    fn dimensions(&self) -> Dimensions {
+        self.dims
+    }
+
+    fn index(&self, x: usize, y: usize) -> Option<usize> {
+        let Dimensions { width, height } = self.dims;
+        if x >= width || y >= height {
+            return None;
+        }
+        Some(match self.order {
+            Order::RowMajor => x + y * width,
+            Order::ColumnMajor => x * height + y,
+        })
+    }
+
+    fn position(&self, index: usize) -> Option<(usize, usize)> {
+        let Dimensions { width, height } = self.dims;
+        if index >= self.dims.num_px() {
+            return None;
+        }
+        Some(match self.order {
+            Order::RowMajor => (index % width, index / width),
+            Order::ColumnMajor => (index / height, index % height),
+        })
+    }
+}
+
+/// A strip of LEDs zigzagged across a grid, so every other row (or column)
+/// runs backwards. The first starts from the left (or top).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Serpentine {
+    pub dims: Dimensions,
+    pub order: Order,
+}
+
+impl PixelMap for Serpentine {
+    fn dimensions(&self) -> Dimensions {
+        self.dims
+    }
+
+    fn index(&self, x: usize, y: usize) -> Option<usize> {
+        let (x, y) = self.unzigzag(x, y);
+        Grid {
+            dims: self.dims,
+            order: self.order,
+        }
+        .index(x, y)
+    }
+
+    fn position(&self, index: usize) -> Option<(usize, usize)> {
+        let (x, y) = Grid {
+            dims: self.dims,
+            order: self.order,
+        }
+        .position(index)?;
+        Some(self.unzigzag(x, y))
+    }
+}
+
+impl Serpentine {
+    /// Reverse the odd rows (or columns), which is its own inverse.
+    fn unzigzag(&self, x: usize, y: usize) -> (usize, usize) {
+        let Dimensions { width, height } = self.dims;
+        match self.order {
+            Order::RowMajor if y % 2 == 1 && x < width => (width - 1 - x, y),
+            Order::ColumnMajor if x % 2 == 1 && y < height => (x, height - 1 - y),
+            _ => (x, y),
+        }
+    }
+}
+
+/// An arbitrary layout from a table of which LED is where, which may have
+/// gaps in either. Loaded from CSV, with a row of cells per row of the display, or
+/// from JSON in WLED's `ledmap.json` format.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LookupMap {
+    dims: Dimensions,
+    /// LED index by `x + y * width`.
+    indices: Vec<Option<usize>>,
+    /// `(x, y)` by LED index.
+    positions: BTreeMap<usize, (usize, usize)>,
+}
+
+impl LookupMap {
+    /// `indices` holds the LED index of each position, row by row.
+    pub fn new(dims: Dimensions, indices: Vec<Option<usize>>) -> Result<Self, Error> {
+        if dims.width.checked_mul(dims.height) != Some(indices.len()) {
+            return Err(Error::InvalidPixelMap(format!(
+                "{} positions given for a {}x{} display",
+                indices.len(),
+                dims.width,
+                dims.height
+            )));
+        }
+
+        let mut positions = BTreeMap::new();
+        for (i, index) in indices.iter().enumerate() {
+            let Some(index) = *index else {
+                continue;
+            };
+            let position = (i % dims.width, i / dims.width);
+            if positions.insert(index, position).is_some() {
+                return Err(Error::InvalidPixelMap(format!(
+                    "LED {} is in more than one place",
+                    index
+                )));
+            }
+        }
+
+        Ok(LookupMap {
+            dims,
+            indices,
+            positions,
+        })
+    }
+
+    /// Read a grid of LED indices, with empty cells or `-1` where there is
+    /// no LED, e.g.
+    ///
+    /// ```text
+    /// 0,1,2
+    /// 5,4,3
+    /// ,6,
+    /// ```
+    pub fn from_csv(reader: impl BufRead) -> Result<Self, Error> {
+        let mut rows = vec![];
+        for line in reader.lines() {
+            let line = line.map_err(Error::Io)?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let row = line
+                .split(',')
+                .map(|cell| parse_index(cell.trim()))
+                .collect::<Result<Vec<_>, _>>()?;
+            rows.push(row);
+        }
+
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let dims = Dimensions {
+            width,
+            height: rows.len(),
+        };
+        let indices = rows
+            .into_iter()
+            .flat_map(|mut row| {
+                row.resize(width, None);
+                row
+            })
+            .collect();
+        Self::new(dims, indices)
+    }
+
+    /// Read a map in WLED's `ledmap.json` format, where `map` gives the LED
+    /// index of each position row by row, or -1 for none, e.g.
+    ///
+    /// ```text
+    /// {"width": 3, "height": 2, "map": [0, 1, 2, 5, 4, 3]}
+    /// ```
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let invalid = |msg: &str| Error::InvalidPixelMap(msg.to_owned());
+        let value: Value = serde_json::from_str(json).map_err(|e| invalid(&e.to_string()))?;
+
+        let map = value
+            .get("map")
+            .and_then(Value::as_array)
+            .ok_or_else(|| invalid("No \"map\" array"))?;
+        let indices = map
+            .iter()
+            .map(|v| match v.as_i64() {
+                Some(n) if n < 0 => Ok(None),
+                Some(n) => usize::try_from(n)
+                    .map(Some)
+                    .map_err(|_| invalid("LED index too large")),
+                None => Err(invalid("The map must be whole numbers")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let dim = |key: &str| {
+            let value = value.get(key)?;
+            let dim = value.as_u64().and_then(|dim| usize::try_from(dim).ok());
+            Some(dim.ok_or_else(|| invalid(&format!("Bad {}: {}", key, value))))
+        };
+        let dims = match (dim("width"), dim("height")) {
+            (Some(width), Some(height)) => Dimensions {
+                width: width?,
+                height: height?,
+            },
+            // WLED assumes a square without them
+            _ => {
+                let side = (indices.len() as f64).sqrt() as usize;
+                Dimensions {
+                    width: side,
+                    height: side,
+                }
+            }
+        };
+        Self::new(dims, indices)
+    }
+
+    /// Load a map from a `.json` or `.csv` file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(Error::Io)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::from_json(&contents),
+            Some("csv") => Self::from_csv(contents.as_bytes()),
+            _ => Err(Error::InvalidPixelMap(format!(
+                "Unknown kind of file: {}",
+                path.display()
+            ))),
+        }
+    }
+}
+
+fn parse_index(cell: &str) -> Result<Option<usize>, Error> {
+    match cell {
+        "" | "-1" => Ok(None),
+        cell => cell
+            .parse()
+            .map(Some)
+            .map_err(|_| Error::InvalidPixelMap(format!("Not an LED index: {:?}", cell))),
+    }
+}
+
+impl PixelMap for LookupMap {
+    fn dimensions(&self) -> Dimensions {
+        self.dims
+    }
+
+    fn index(&self, x: usize, y: usize) -> Option<usize> {
+        if x >= self.dims.width {
+            return None;
+        }
+        *self.indices.get(x + y * self.dims.width)?
+    }
+
+    fn position(&self, index: usize) -> Option<(usize, usize)> {
+        self.positions.get(&index).copied()
+    }
+}
+
+/// A display whose LEDs are laid out by `map`, so [`Display::set_xy`]
+/// lights the LED at that position. Indices are passed straight through.
+///
+/// Boards take a map of their own with `set_pixel_map`; this is for
+/// anything else, such as the emulators.
+pub struct Mapped<T: Display, M: PixelMap> {
+    display: T,
+    map: M,
+    dims: Dimensions,
+}
+
+impl<T: Display, M: PixelMap> Mapped<T, M> {
+    /// Fails if the map places LEDs past the end of `display`.
+    pub fn new(display: T, map: M) -> Result<Self, Error> {
+        check_fits(&map, display.num_leds())?;
+        let dims = map.dimensions();
+        Ok(Mapped { display, map, dims })
+    }
+
+    pub fn map(&self) -> &M {
+        &self.map
+    }
+
+    pub fn into_inner(self) -> T {
+        self.display
+    }
+}
+
+impl<T: Display, M: PixelMap> Display for Mapped<T, M> {
+    /// Positions without an LED are skipped.
+    fn set_xy(&mut self, x: usize, y: usize, rgb: &RGB8) {
+        assert!(x < self.dims.width, "LED x index out of range: {}", x);
+        assert!(y < self.dims.height, "LED y index out of range: {}", y);
+
+        if let Some(idx) = self.map.index(x, y) {
+            self.display.set_idx(idx, rgb);
+        }
+    }
+
+    fn set_idx(&mut self, idx: usize, rgb: &RGB8) {
+        self.display.set_idx(idx, rgb);
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.display.flush()
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        self.display.reset()
+    }
+
+    fn dimensions(&self) -> &Dimensions {
+        &self.dims
+    }
+
+    fn num_leds(&self) -> usize {
+        self.display.num_leds()
+    }
+
+    fn set_brightness(&mut self, brightness: f32) {
+        self.display.set_brightness(brightness);
+    }
+
+    fn brightness(&self) -> f32 {
+        self.display.brightness()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::SharedDisplay;
+
+    fn round_trips(map: &dyn PixelMap) {
+        let Dimensions { width, height } = map.dimensions();
+        for y in 0..height {
+            for x in 0..width {
+                if let Some(idx) = map.index(x, y) {
+                    assert_eq!(map.position(idx), Some((x, y)));
+                }
+            }
+        }
+        assert_eq!(map.index(width, 0), None);
+        assert_eq!(map.index(0, height), None);
+    }
+
+    #[test]
+    fn test_boards() {
+        assert_eq!(Grid::UNICORN.index(3, 2), Some(3 + 2 * 16));
+        assert_eq!(Grid::UNICORN_MINI.index(3, 2), Some(3 * 7 + 2));
+        assert_eq!(Grid::UNICORN_MINI.position(118), Some((16, 6)));
+        assert_eq!(Grid::UNICORN_MINI.position(119), None);
+        assert_eq!(
+            Grid::for_dimensions(Dimensions::UNICORN_MINI),
+            Grid::UNICORN_MINI
+        );
+        assert_eq!(Grid::for_dimensions(Dimensions::UNICORN), Grid::UNICORN);
+        round_trips(&Grid::UNICORN);
+        round_trips(&Grid::UNICORN_MINI);
+    }
+
+    #[test]
+    fn test_serpentine() {
+        let dims = Dimensions {
+            width: 3,
+            height: 3,
+        };
+        let rows = Serpentine {
+            dims,
+            order: Order::RowMajor,
+        };
+        let indices = |map: &Serpentine| {
+            (0..3)
+                .flat_map(|y| (0..3).map(move |x| (x, y)))
+                .map(|(x, y)| map.index(x, y).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(indices(&rows), [0, 1, 2, 5, 4, 3, 6, 7, 8]);
+
+        let columns = Serpentine {
+            dims,
+            order: Order::ColumnMajor,
+        };
+        assert_eq!(indices(&columns), [0, 5, 6, 1, 4, 7, 2, 3, 8]);
+        round_trips(&rows);
+        round_trips(&columns);
+    }
+
+    #[test]
+    fn test_csv() {
+        let map = LookupMap::from_csv("0,1,2\n5,4,3\n,6\n".as_bytes()).unwrap();
+
+        assert_eq!(
+            map.dimensions(),
+            Dimensions {
+                width: 3,
+                height: 3
+            }
+        );
+        assert_eq!(map.index(0, 1), Some(5));
+        assert_eq!(map.index(0, 2), None);
+        assert_eq!(map.index(2, 2), None);
+        assert_eq!(map.position(6), Some((1, 2)));
+        round_trips(&map);
+
+        assert!(LookupMap::from_csv("0,x".as_bytes()).is_err());
+        assert!(LookupMap::from_csv("0,0".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_sparse_indices() {
+        // Skipping LEDs, like a window onto part of a bigger display
+        let map = LookupMap::from_csv("0,2".as_bytes()).unwrap();
+        assert_eq!(map.index(1, 0), Some(2));
+        assert_eq!(map.position(2), Some((1, 0)));
+        assert_eq!(map.position(1), None);
+        assert!(Mapped::new(SharedDisplay::new(Dimensions::UNICORN), map).is_ok());
+
+        // Only the display knows how many LEDs there really are
+        let map = LookupMap::from_csv("0,4000000000".as_bytes()).unwrap();
+        assert_eq!(map.position(4000000000), Some((1, 0)));
+        assert!(matches!(
+            Mapped::new(SharedDisplay::new(Dimensions::UNICORN), map),
+            Err(Error::InvalidPixelMap(_))
+        ));
+        assert!(LookupMap::from_csv("0,18446744073709551616".as_bytes()).is_err());
+        assert!(LookupMap::from_json(r#"{"map": [0, 4000000000, 2, 3]}"#).is_ok());
+
+        for json in [
+            r#"{"map": [0, 1e300, 2, 3]}"#,
+            r#"{"map": [0, 18446744073709551615, 2, 3]}"#,
+            r#"{"width": 18446744073709551615, "height": 2, "map": [0, 1]}"#,
+        ] {
+            assert!(matches!(
+                LookupMap::from_json(json),
+                Err(Error::InvalidPixelMap(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_json() {
+        let map = LookupMap::from_json(
+            r#"{"n": "Sideways \"strip\"", "width": 2, "height": 3, "map": [
+                4, 5,
+                3, 2,
+                0, -1
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            map.dimensions(),
+            Dimensions {
+                width: 2,
+                height: 3
+            }
+        );
+        assert_eq!(map.index(1, 0), Some(5));
+        assert_eq!(map.index(1, 2), None);
+        assert_eq!(map.position(1), None);
+        round_trips(&map);
+
+        // Square without dimensions
+        let map = LookupMap::from_json(r#"{"map": [3, 2, 1, 0]}"#).unwrap();
+        assert_eq!(map.index(1, 1), Some(0));
+
+        assert!(LookupMap::from_json(r#"{"width": 2}"#).is_err());
+        assert!(LookupMap::from_json(r#"{"map": [1, 2.5]}"#).is_err());
+        assert!(LookupMap::from_json(r#"{"map": [0, 1, 2, 3]"#).is_err());
+    }
+
+    #[test]
+    fn test_mapped_display() {
+        let strip = SharedDisplay::new(Dimensions {
+            width: 6,
+            height: 1,
+        });
+        let map = Serpentine {
+            dims: Dimensions {
+                width: 3,
+                height: 2,
+            },
+            order: Order::RowMajor,
+        };
+        let mut display = Mapped::new(strip.clone(), map).unwrap();
+
+        assert_eq!(display.dimensions().height, 2);
+        display.set_xy(0, 1, &RGB8::new(1, 2, 3));
+        assert_eq!(strip.pixels()[5], RGB8::new(1, 2, 3));
+
+        // A strip too short for the map
+        let short = SharedDisplay::new(Dimensions {
+            width: 5,
+            height: 1,
+        });
+        assert!(matches!(
+            Mapped::new(short, map),
+            Err(Error::InvalidPixelMap(_))
+        ));
+    }
+}
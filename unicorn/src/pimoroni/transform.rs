@@ -0,0 +1,262 @@
+use rgb::RGB8;
+
+use super::{Dimensions, Display};
+use crate::error::Error;
+
+/// Clockwise rotation of the picture on the panel.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    R0,
+    R90,
+    R180,
+    R270,
+}
+
+/// How a panel is mounted, so drawing can be done the right way up. Flips
+/// are applied to the picture before it's rotated.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Transform {
+    pub rotation: Rotation,
+    /// Mirror left to right.
+    pub flip_horizontal: bool,
+    /// Mirror top to bottom.
+    pub flip_vertical: bool,
+}
+
+impl Transform {
+    pub fn rotate(rotation: Rotation) -> Self {
+        Transform {
+            rotation,
+            ..Default::default()
+        }
+    }
+
+    /// Swap x and y, mirroring about the top-left to bottom-right diagonal.
+    pub fn transpose() -> Self {
+        Transform {
+            rotation: Rotation::R90,
+            flip_vertical: true,
+            ..Default::default()
+        }
+    }
+
+    /// Whether width and height are swapped.
+    fn swaps_axes(&self) -> bool {
+        matches!(self.rotation, Rotation::R90 | Rotation::R270)
+    }
+
+    /// Dimensions drawn in, on a panel of `physical` dimensions.
+    pub fn logical(&self, physical: Dimensions) -> Dimensions {
+        if self.swaps_axes() {
+            Dimensions {
+                width: physical.height,
+                height: physical.width,
+            }
+        } else {
+            physical
+        }
+    }
+
+    /// Where on a panel of `physical` dimensions the logical `(x, y)` is.
+    pub fn apply(&self, x: usize, y: usize, physical: Dimensions) -> (usize, usize) {
+        let logical = self.logical(physical);
+        let x = if self.flip_horizontal {
+            logical.width - 1 - x
+        } else {
+            x
+        };
+        let y = if self.flip_vertical {
+            logical.height - 1 - y
+        } else {
+            y
+        };
+
+        let Dimensions { width, height } = physical;
+        match self.rotation {
+            Rotation::R0 => (x, y),
+            Rotation::R90 => (width - 1 - y, x),
+            Rotation::R180 => (width - 1 - x, height - 1 - y),
+            Rotation::R270 => (y, height - 1 - x),
+        }
+    }
+}
+
+/// A display drawn on the right way up, whichever way it's mounted.
+///
+/// Coordinates are logical, within [`Display::dimensions`], which are the
+/// panel's with width and height swapped for quarter turns. Indices count
+/// along the logical rows, as `x + y * width`, whatever order the panel
+/// itself uses.
+pub struct Transformed<T: Display> {
+    display: T,
+    transform: Transform,
+    dims: Dimensions,
+}
+
+impl<T: Display> Transformed<T> {
+    pub fn new(display: T, transform: Transform) -> Self {
+        let dims = transform.logical(*display.dimensions());
+        Transformed {
+            display,
+            transform,
+            dims,
+        }
+    }
+
+    pub fn transform(&self) -> Transform {
+        self.transform
+    }
+
+    /// Change the transform, e.g. when the display is turned. Pixels already
+    /// drawn stay where they are on the panel.
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+        self.dims = transform.logical(*self.display.dimensions());
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.display
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.display
+    }
+
+    pub fn into_inner(self) -> T {
+        self.display
+    }
+}
+
+impl<T: Display> Display for Transformed<T> {
+    fn set_xy(&mut self, x: usize, y: usize, rgb: &RGB8) {
+        assert!(
+            x < self.dims.width && y < self.dims.height,
+            "({}, {}) is off the {}x{} display",
+            x,
+            y,
+            self.dims.width,
+            self.dims.height
+        );
+        let (x, y) = self.transform.apply(x, y, *self.display.dimensions());
+        self.display.set_xy(x, y, rgb);
+    }
+
+    fn set_idx(&mut self, idx: usize, rgb: &RGB8) {
+        self.set_xy(idx % self.dims.width, idx / self.dims.width, rgb);
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.display.flush()
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        self.display.reset()
+    }
+
+    fn dimensions(&self) -> &Dimensions {
+        &self.dims
+    }
+
+    fn set_brightness(&mut self, brightness: f32) {
+        self.display.set_brightness(brightness);
+    }
+
+    fn brightness(&self) -> f32 {
+        self.display.brightness()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::SharedDisplay;
+
+    const PANEL: Dimensions = Dimensions {
+        width: 3,
+        height: 2,
+    };
+
+    /// Where each logical pixel lands, by its index on the row-major panel.
+    fn layout(transform: Transform) -> Vec<u8> {
+        let panel = SharedDisplay::new(PANEL);
+        let mut display = Transformed::new(panel.clone(), transform);
+        let dims = *display.dimensions();
+        for idx in 0..dims.num_px() {
+            display.set_idx(idx, &RGB8::new(idx as u8 + 1, 0, 0));
+        }
+        panel.pixels().iter().map(|p| p.r).collect()
+    }
+
+    #[test]
+    fn test_rotations() {
+        // Logical pixels numbered 1.. along the rows
+        assert_eq!(layout(Transform::default()), [1, 2, 3, 4, 5, 6]);
+        // 1 2
+        // 3 4  turned clockwise is  5 3 1
+        // 5 6                       6 4 2
+        assert_eq!(layout(Transform::rotate(Rotation::R90)), [5, 3, 1, 6, 4, 2]);
+        assert_eq!(
+            layout(Transform::rotate(Rotation::R180)),
+            [6, 5, 4, 3, 2, 1]
+        );
+        assert_eq!(
+            layout(Transform::rotate(Rotation::R270)),
+            [2, 4, 6, 1, 3, 5]
+        );
+    }
+
+    #[test]
+    fn test_flips() {
+        let flip_h = Transform {
+            flip_horizontal: true,
+            ..Default::default()
+        };
+        let flip_v = Transform {
+            flip_vertical: true,
+            ..Default::default()
+        };
+        assert_eq!(layout(flip_h), [3, 2, 1, 6, 5, 4]);
+        assert_eq!(layout(flip_v), [4, 5, 6, 1, 2, 3]);
+
+        // Flipping both ways is half a turn
+        let both = Transform {
+            flip_horizontal: true,
+            flip_vertical: true,
+            ..Default::default()
+        };
+        assert_eq!(layout(both), layout(Transform::rotate(Rotation::R180)));
+    }
+
+    #[test]
+    fn test_transpose() {
+        assert_eq!(layout(Transform::transpose()), [1, 3, 5, 2, 4, 6]);
+
+        let transform = Transform::transpose();
+        for (x, y) in [(0, 0), (1, 0), (0, 2), (1, 2)] {
+            assert_eq!(transform.apply(x, y, PANEL), (y, x));
+        }
+    }
+
+    #[test]
+    fn test_dimensions_and_delegation() {
+        let panel = SharedDisplay::new(PANEL);
+        let mut display = Transformed::new(panel.clone(), Transform::rotate(Rotation::R90));
+        assert_eq!(
+            *display.dimensions(),
+            Dimensions {
+                width: 2,
+                height: 3
+            }
+        );
+        assert!(display.try_set_xy(1, 2, &RGB8::new(1, 1, 1)).is_ok());
+        assert!(display.try_set_xy(2, 0, &RGB8::new(1, 1, 1)).is_err());
+
+        display.flush().unwrap();
+        assert_eq!(panel.flushes(), 1);
+
+        display.set_transform(Transform::default());
+        assert_eq!(*display.dimensions(), PANEL);
+        assert_eq!(display.into_inner().dimensions(), &PANEL);
+    }
+}
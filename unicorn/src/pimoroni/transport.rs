@@ -0,0 +1,69 @@
+use std::io::{self, Write};
+
+use spidev::{Spidev, SpidevOptions};
+
+use crate::error::Error;
+
+/// Byte sink the display drivers write their SPI frames to.
+///
+/// Implemented for [`Spidev`] so the drivers talk to real hardware by default,
+/// and by [`RecordingTransport`] so frames can be inspected off a Pi.
+pub trait Transport {
+    /// Write `data` in a single transfer, returning the number of bytes written.
+    fn write(&mut self, data: &[u8]) -> io::Result<usize>;
+}
+
+impl Transport for Spidev {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        Write::write(self, data)
+    }
+}
+
+/// Open and configure the spidev node at `path`.
+pub(crate) fn open_spidev(path: &str, options: &SpidevOptions) -> Result<Spidev, Error> {
+    let mut spi = Spidev::open(path).map_err(|e| Error::open(path, e))?;
+    spi.configure(options).map_err(|source| Error::SpiConfig {
+        path: path.to_owned(),
+        source,
+    })?;
+    Ok(spi)
+}
+
+/// Write `data` in one transfer, treating a partial transfer as an error.
+pub(crate) fn send<T: Transport>(spi: &mut T, data: &[u8]) -> Result<(), Error> {
+    let written = spi.write(data).map_err(Error::SpiWrite)?;
+    if written != data.len() {
+        return Err(Error::ShortWrite {
+            expected: data.len(),
+            written,
+        });
+    }
+    Ok(())
+}
+
+/// In-memory transport which keeps a copy of every transfer.
+#[derive(Debug, Default, Clone)]
+pub struct RecordingTransport {
+    writes: Vec<Vec<u8>>,
+}
+impl RecordingTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every transfer so far, oldest first.
+    pub fn writes(&self) -> &[Vec<u8>] {
+        &self.writes
+    }
+
+    pub fn clear(&mut self) {
+        self.writes.clear();
+    }
+}
+
+impl Transport for RecordingTransport {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.writes.push(data.to_owned());
+        Ok(data.len())
+    }
+}
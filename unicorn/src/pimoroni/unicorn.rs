@@ -1,42 +1,84 @@
-use std::{io::Write, time::Duration};
+use std::time::Duration;
 
 use rgb::RGB8;
 use spidev::{SpiModeFlags, Spidev, SpidevOptions};
 
-use super::{Dimensions, Display};
+use super::{
+    colour::ColourPipeline,
+    pixelmap::{self, Grid, PixelMap},
+    transport::{self, Transport},
+    Dimensions, Display,
+};
+use crate::error::Error;
 
 // Based on: https://github.com/pimoroni/unicorn-hat-hd/blob/master/library/unicornhathd/__init__.py
 
 const SOF: u8 = 0x72;
-const BUF_SIZE: usize = 256 * 3 + 1;
+const NUM_LEDS: usize = 256;
+const BUF_SIZE: usize = NUM_LEDS * 3 + 1;
 const DELAY: u64 = 9;
-pub struct Unicorn {
-    spi: Spidev,
+pub struct Unicorn<T: Transport = Spidev> {
+    spi: T,
     buffer: [u8; BUF_SIZE],
+    map: Box<dyn PixelMap>,
     dims: Dimensions,
+    brightness: f32,
+    pipeline: ColourPipeline,
 }
 
 impl Unicorn {
+    /// Open the HAT on `/dev/spidev0.0`, panicking on failure.
     pub fn new() -> Self {
-        let mut spi = Spidev::open("/dev/spidev0.0")
-            .expect("Do you have sufficient permissions to '/dev/spidev0.0' ?");
+        Self::try_new().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_new() -> Result<Self, Error> {
+        Self::try_with_pipeline(ColourPipeline::default())
+    }
+
+    /// Open the HAT, correcting every frame with `pipeline`.
+    pub fn try_with_pipeline(pipeline: ColourPipeline) -> Result<Self, Error> {
         let options = SpidevOptions::new()
             .bits_per_word(8)
             .max_speed_hz(9_000_000)
             .mode(SpiModeFlags::SPI_MODE_0)
             .build();
-        spi.configure(&options).expect("SPI config error");
+        let spi = transport::open_spidev("/dev/spidev0.0", &options)?;
+
+        Self::with_transport(spi, pipeline)
+    }
+}
 
+impl<T: Transport> Unicorn<T> {
+    pub fn with_transport(spi: T, pipeline: ColourPipeline) -> Result<Self, Error> {
         let mut display = Unicorn {
             spi,
             buffer: [0; BUF_SIZE],
-            dims: Dimensions {
-                width: 16,
-                height: 16,
-            },
+            map: Box::new(Grid::UNICORN),
+            dims: Dimensions::UNICORN,
+            brightness: 1.0,
+            pipeline,
         };
-        display.reset();
-        display
+        display.reset()?;
+        Ok(display)
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.spi
+    }
+
+    /// Lay the LEDs out with `map` rather than [`Grid::UNICORN`], e.g. for a
+    /// panel built from the same driver. Changes the display's dimensions to
+    /// the map's, and fails if it places LEDs past the 256 there are.
+    pub fn set_pixel_map(&mut self, map: impl PixelMap + 'static) -> Result<(), Error> {
+        pixelmap::check_fits(&map, NUM_LEDS)?;
+        self.dims = map.dimensions();
+        self.map = Box::new(map);
+        Ok(())
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.spi
     }
 }
 
@@ -46,22 +88,24 @@ impl Default for Unicorn {
     }
 }
 
-impl Drop for Unicorn {
+impl<T: Transport> Drop for Unicorn<T> {
     fn drop(&mut self) {
-        self.reset();
+        self.reset().ok();
     }
 }
 
-impl Display for Unicorn {
+impl<T: Transport> Display for Unicorn<T> {
     fn set_xy(&mut self, x: usize, y: usize, rgb: &RGB8) {
-        let idx = x + y * 16;
-        assert!(x < 16, "LED x index out of range: {}", idx);
-        assert!(y < 16, "LED y index out of range: {}", idx);
+        assert!(x < self.dims.width, "LED x index out of range: {}", x);
+        assert!(y < self.dims.height, "LED y index out of range: {}", y);
 
-        self.set_idx(idx, rgb);
+        if let Some(idx) = self.map.index(x, y) {
+            self.set_idx(idx, rgb);
+        }
     }
 
     fn set_idx(&mut self, idx: usize, rgb: &RGB8) {
+        assert!(idx < NUM_LEDS, "LED index out of range: {}", idx);
         // Buffer indexes are offset by 1 because of 0x72 at start
         let i = idx * 3 + 1;
         self.buffer[i] = rgb.r;
@@ -69,28 +113,58 @@ impl Display for Unicorn {
         self.buffer[i + 2] = rgb.b;
     }
 
-    fn flush(&mut self) {
-        self.spi.write_all(&self.buffer).expect("SPI write error");
+    fn flush(&mut self) -> Result<(), Error> {
+        // The HD has no hardware brightness control, so scale the colours
+        // before they are corrected
+        let scale = |v: u8| (v as f32 * self.brightness).round() as u8;
+        let mut frame = self.buffer;
+        for px in frame[1..].chunks_exact_mut(3) {
+            let rgb = RGB8::new(scale(px[0]), scale(px[1]), scale(px[2]));
+            let rgb = self.pipeline.apply(&rgb);
+            px.copy_from_slice(&[rgb.r, rgb.g, rgb.b]);
+        }
+        transport::send(&mut self.spi, &frame)?;
         std::thread::sleep(Duration::from_millis(DELAY));
+        Ok(())
     }
 
-    fn reset(&mut self) {
+    fn reset(&mut self) -> Result<(), Error> {
         self.buffer = [0; BUF_SIZE];
         self.buffer[0] = SOF;
-        self.flush();
+        self.flush()
     }
 
     fn dimensions(&self) -> &Dimensions {
         &self.dims
     }
+
+    fn num_leds(&self) -> usize {
+        NUM_LEDS
+    }
+
+    fn set_brightness(&mut self, brightness: f32) {
+        self.brightness = brightness.clamp(0.0, 1.0);
+    }
+
+    fn brightness(&self) -> f32 {
+        self.brightness
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Display, Unicorn, RGB8};
-    use std::time::Duration;
+    use super::{Display, Unicorn, BUF_SIZE, RGB8, SOF};
+    use crate::{
+        error::Error,
+        pimoroni::{
+            colour::ColourPipeline,
+            transport::{RecordingTransport, Transport},
+        },
+    };
+    use std::{io, time::Duration};
 
     #[test]
+    #[ignore = "needs a Unicorn HAT HD on /dev/spidev0.0"]
     fn test_unicorn() {
         let mut display = Unicorn::new();
         let r = RGB8::new(255, 0, 0);
@@ -108,7 +182,115 @@ mod tests {
         display.set_xy(4, 2, &b);
         display.set_xy(4, 3, &b);
         display.set_xy(4, 4, &b);
-        display.flush();
+        display.flush().unwrap();
         std::thread::sleep(Duration::from_millis(10000));
     }
+
+    fn blank_frame() -> Vec<u8> {
+        let mut frame = vec![0; BUF_SIZE];
+        frame[0] = SOF;
+        frame
+    }
+
+    #[test]
+    fn test_reset_sends_blank_frame() {
+        let display =
+            Unicorn::with_transport(RecordingTransport::new(), ColourPipeline::default()).unwrap();
+
+        assert_eq!(display.transport().writes(), &[blank_frame()]);
+    }
+
+    #[test]
+    fn test_flush_frame() {
+        let mut display =
+            Unicorn::with_transport(RecordingTransport::new(), ColourPipeline::default()).unwrap();
+        display.transport_mut().clear();
+
+        display.set_xy(0, 0, &RGB8::new(1, 2, 3));
+        display.set_xy(4, 1, &RGB8::new(4, 5, 6));
+        display.set_idx(255, &RGB8::new(7, 8, 9));
+        display.flush().unwrap();
+
+        let mut expected = blank_frame();
+        expected[1..4].copy_from_slice(&[1, 2, 3]);
+        expected[61..64].copy_from_slice(&[4, 5, 6]);
+        expected[766..769].copy_from_slice(&[7, 8, 9]);
+        assert_eq!(display.transport().writes(), &[expected]);
+    }
+
+    #[test]
+    #[should_panic(expected = "LED x index out of range")]
+    fn test_set_xy_out_of_range() {
+        let mut display =
+            Unicorn::with_transport(RecordingTransport::new(), ColourPipeline::default()).unwrap();
+        display.set_xy(16, 0, &RGB8::new(1, 1, 1));
+    }
+
+    #[test]
+    fn test_try_set_out_of_range() {
+        let mut display =
+            Unicorn::with_transport(RecordingTransport::new(), ColourPipeline::default()).unwrap();
+
+        assert!(matches!(
+            display.try_set_xy(0, 16, &RGB8::new(1, 1, 1)),
+            Err(Error::CoordinateOutOfRange { x: 0, y: 16, .. })
+        ));
+        assert!(matches!(
+            display.try_set_idx(256, &RGB8::new(1, 1, 1)),
+            Err(Error::IndexOutOfRange {
+                idx: 256,
+                num_px: 256
+            })
+        ));
+        assert!(display.try_set_idx(255, &RGB8::new(1, 1, 1)).is_ok());
+    }
+
+    struct ShortTransport;
+    impl Transport for ShortTransport {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            Ok(data.len() / 2)
+        }
+    }
+
+    #[test]
+    fn test_short_write() {
+        let result = Unicorn::with_transport(ShortTransport, ColourPipeline::default());
+
+        assert!(matches!(
+            result,
+            Err(Error::ShortWrite {
+                expected: BUF_SIZE,
+                written: 384
+            })
+        ));
+    }
+
+    #[test]
+    fn test_brightness_scales_colours() {
+        let mut display =
+            Unicorn::with_transport(RecordingTransport::new(), ColourPipeline::default()).unwrap();
+        display.transport_mut().clear();
+
+        display.set_idx(0, &RGB8::new(255, 100, 1));
+        display.set_brightness(0.5);
+        display.flush().unwrap();
+
+        let frame = &display.transport().writes()[0];
+        assert_eq!(frame[0], SOF);
+        assert_eq!(frame[1..4], [128, 50, 1]);
+    }
+
+    #[test]
+    fn test_pipeline_applied_after_brightness() {
+        let pipeline = ColourPipeline::new(2.0, [1.0; 3], u8::MAX);
+        let mut display = Unicorn::with_transport(RecordingTransport::new(), pipeline).unwrap();
+        display.transport_mut().clear();
+
+        display.set_idx(1, &RGB8::new(255, 0, 255));
+        display.set_brightness(0.5);
+        display.flush().unwrap();
+
+        let frame = &display.transport().writes()[0];
+        assert_eq!(frame[4..7], [64, 0, 64]);
+    }
 }
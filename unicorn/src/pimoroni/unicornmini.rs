@@ -1,15 +1,19 @@
 use rgb::RGB8;
-use rppal::gpio::{Gpio, InputPin, Trigger};
 use spidev::{SpiModeFlags, Spidev, SpidevOptions};
-use std::{
-    cell::RefCell,
-    io::Write,
-    ops::Range,
-    time::{Duration, SystemTime},
-};
-use tokio::{runtime::Runtime, sync::watch::Receiver, task::JoinHandle};
+use std::{ops::Range, sync::Weak};
 
-use super::{Dimensions, Display};
+pub use super::buttons::{
+    Button, ButtonEvent, ButtonInput, ButtonSettings, Buttons, Edge, Encoder, EncoderEvent,
+    EventKind, GpioButtons, InputEvent, InputMap, ScriptedButtons,
+};
+use super::{
+    buttons::Shared,
+    colour::ColourPipeline,
+    pixelmap::{self, Grid, PixelMap},
+    transport::{self, Transport},
+    Dimensions, Display,
+};
+use crate::error::Error;
 
 // Based on:
 // https://github.com/pimoroni/unicornhatmini-python/blob/master/library/unicornhatmini/__init__.py
@@ -25,7 +29,11 @@ use super::{Dimensions, Display};
 
 // Holtek HT16D35 to drive the LEDs
 const CMD_SOFT_RESET: [u8; 1] = [0xCC];
-const CMD_GLOBAL_BRIGHTNESS: [u8; 2] = [0x37, 0x01];
+const CMD_GLOBAL_BRIGHTNESS: u8 = 0x37;
+// Brightness is a 6 bit value
+const MAX_BRIGHTNESS: u8 = 63;
+// The lowest level, as Pimoroni's library starts at
+const DEFAULT_BRIGHTNESS: u8 = 0x01;
 const CMD_COM_PIN_CTRL: [u8; 2] = [0x41, 0xff];
 const CMD_ROW_PIN_CTRL: [u8; 5] = [0x42, 0xff, 0xff, 0xff, 0xff];
 const CMD_WRITE_DISPLAY: [u8; 2] = [0x80, 0x00];
@@ -34,6 +42,9 @@ const CMD_SYSTEM_CTRL_OFF: [u8; 2] = [0x35, 0x00];
 const CMD_SYSTEM_CTRL_ON: [u8; 2] = [0x35, 0x03];
 const CMD_SCROLL_CTRL: [u8; 2] = [0x20, 0x00];
 
+/// Offsets of the red, green and blue bytes of each LED in `data_buf`. This
+/// is how the chips are wired, so stays the same whichever pixel map lays
+/// the LEDs out; [`Grid::UNICORN_MINI`] is the board's own.
 const LUT: [[usize; 3]; 119] = [
     [139, 138, 137],
     [223, 222, 221],
@@ -158,128 +169,144 @@ const LUT: [[usize; 3]; 119] = [
 const BUF_SIZE: usize = 28 * 8;
 pub const NUM_LEDS: usize = 119;
 
-#[derive(Debug)]
-pub enum Button {
-    A,
-    B,
-    X,
-    Y,
-}
-impl Button {
-    pub fn pin(&self) -> u8 {
-        match self {
-            Button::A => 5,
-            Button::B => 6,
-            Button::X => 16,
-            Button::Y => 24,
-        }
-    }
-}
-
-pub struct UnicornMini {
+pub struct UnicornMini<T: Transport = Spidev> {
     data_buf: [u8; BUF_SIZE * 2],
-    spi: [Spidev; 2],
-    button_rx: RefCell<Option<Receiver<Option<Button>>>>,
+    spi: [T; 2],
+    /// The running button watch, if anything still has a handle on it.
+    buttons: Weak<Shared>,
+    button_input: Option<Box<dyn ButtonInput>>,
+    button_settings: ButtonSettings,
+    input_map: InputMap,
+    map: Box<dyn PixelMap>,
     dims: Dimensions,
+    brightness: u8,
+    brightness_changed: bool,
+    pipeline: ColourPipeline,
 }
 impl UnicornMini {
+    /// Open both HT16D35 chips on `/dev/spidev0.0` and `/dev/spidev0.1`,
+    /// panicking on failure.
     pub fn new() -> Self {
-        fn get_spi(address: &str) -> Spidev {
-            let mut spi = Spidev::open(address).unwrap();
-            let options = SpidevOptions::new()
-                .max_speed_hz(600_000)
-                .bits_per_word(8)
-                .mode(SpiModeFlags::SPI_MODE_0)
-                .build();
-            spi.configure(&options).expect("SPI config error");
-            spi
-        }
+        Self::try_new().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_new() -> Result<Self, Error> {
+        Self::try_with_pipeline(ColourPipeline::default())
+    }
 
+    /// Open the HAT, correcting every frame with `pipeline`.
+    pub fn try_with_pipeline(pipeline: ColourPipeline) -> Result<Self, Error> {
+        let options = SpidevOptions::new()
+            .max_speed_hz(600_000)
+            .bits_per_word(8)
+            .mode(SpiModeFlags::SPI_MODE_0)
+            .build();
+
+        Self::with_transports(
+            [
+                transport::open_spidev("/dev/spidev0.0", &options)?,
+                transport::open_spidev("/dev/spidev0.1", &options)?,
+            ],
+            pipeline,
+        )
+    }
+}
+
+impl<T: Transport> UnicornMini<T> {
+    /// Drive the two HT16D35 chips through `spi`, first chip first.
+    pub fn with_transports(spi: [T; 2], pipeline: ColourPipeline) -> Result<Self, Error> {
         let mut um = Self {
             data_buf: [0; BUF_SIZE * 2],
-            spi: [get_spi("/dev/spidev0.0"), get_spi("/dev/spidev0.1")],
-            button_rx: RefCell::new(None),
-            dims: Dimensions {
-                width: 17,
-                height: 7,
-            },
+            spi,
+            buttons: Weak::new(),
+            button_input: None,
+            button_settings: ButtonSettings::default(),
+            input_map: InputMap::default(),
+            map: Box::new(Grid::UNICORN_MINI),
+            dims: Dimensions::UNICORN_MINI,
+            brightness: DEFAULT_BRIGHTNESS,
+            brightness_changed: false,
+            pipeline,
         };
 
-        um.reset();
+        um.reset()?;
 
-        um
+        Ok(um)
     }
 
-    fn start_button_watch(runtime: &Runtime) -> Receiver<Option<Button>> {
-        let (tx, rx) = tokio::sync::watch::channel(None);
+    pub fn transports(&self) -> &[T; 2] {
+        &self.spi
+    }
 
-        let _guard = runtime.enter();
-        let _: JoinHandle<()> = tokio::task::spawn_blocking(move || {
-            let mut gpio = Gpio::new().unwrap();
+    pub fn transports_mut(&mut self) -> &mut [T; 2] {
+        &mut self.spi
+    }
 
-            fn get_pin(gpio: &mut Gpio, id: u8) -> InputPin {
-                let mut pin = gpio.get(id).unwrap().into_input_pullup();
-                pin.set_interrupt(Trigger::Both).unwrap();
-                pin
-            }
+    /// Lay the LEDs out with `map` rather than [`Grid::UNICORN_MINI`]. Changes
+    /// the display's dimensions to the map's, and fails if it places LEDs
+    /// past the 119 there are.
+    pub fn set_pixel_map(&mut self, map: impl PixelMap + 'static) -> Result<(), Error> {
+        pixelmap::check_fits(&map, NUM_LEDS)?;
+        self.dims = map.dimensions();
+        self.map = Box::new(map);
+        Ok(())
+    }
 
-            let pins = [
-                get_pin(&mut gpio, Button::A.pin()),
-                get_pin(&mut gpio, Button::B.pin()),
-                get_pin(&mut gpio, Button::X.pin()),
-                get_pin(&mut gpio, Button::Y.pin()),
-            ];
-
-            let p: [&InputPin; 4] = [&pins[0], &pins[1], &pins[2], &pins[3]];
-
-            let mut prev_time = SystemTime::now();
-
-            loop {
-                let result = gpio.poll_interrupts(&p, true, None);
-
-                let elapsed = prev_time.elapsed().unwrap_or_default();
-
-                if elapsed > Duration::from_millis(500) {
-                    prev_time = SystemTime::now();
-                    let result = result.unwrap();
-                    let (pressed_pin, _) = result.as_ref().unwrap();
-
-                    if *pressed_pin == p[0] {
-                        tx.send(Button::A.into()).unwrap();
-                    } else if *pressed_pin == p[1] {
-                        tx.send(Button::B.into()).unwrap();
-                    } else if *pressed_pin == p[2] {
-                        tx.send(Button::X.into()).unwrap();
-                    } else if *pressed_pin == p[3] {
-                        tx.send(Button::Y.into()).unwrap();
-                    }
-                }
-            }
-        });
+    /// Thresholds used to decode button presses, which only take effect if
+    /// set before the watch starts.
+    pub fn set_button_settings(&mut self, settings: ButtonSettings) {
+        self.button_settings = settings;
+    }
 
-        rx
+    /// Buttons and encoders to watch besides, or instead of, A/B/X/Y, which
+    /// only takes effect if set before the watch starts.
+    pub fn set_input_map(&mut self, map: InputMap) {
+        self.input_map = map;
     }
 
-    pub fn button_subscribe(&mut self, runtime: &Runtime) -> Receiver<Option<Button>> {
-        let mut ref_mut = self.button_rx.borrow_mut();
+    /// Read the buttons from `input` rather than the Pi's GPIO pins, which
+    /// only takes effect if set before the watch starts.
+    pub fn set_button_input(&mut self, input: impl ButtonInput + 'static) {
+        self.button_input = Some(Box::new(input));
+    }
+
+    /// Watch the buttons from a background thread, which stops once every
+    /// handle on it has been dropped. Handles share one watch while it runs.
+    pub fn buttons(&mut self) -> Result<Buttons, Error> {
+        if let Some(buttons) = Buttons::from_shared(&self.buttons) {
+            return Ok(buttons);
+        }
+
+        let input = match self.button_input.take() {
+            Some(input) => input,
+            None => Box::new(GpioButtons::with_map(&self.input_map)?),
+        };
+        let buttons = Buttons::start(input, self.button_settings, &self.input_map.encoders)?;
+        self.buttons = buttons.downgrade();
+        Ok(buttons)
+    }
 
-        if let Some(rx) = &*ref_mut {
-            rx.clone()
-        } else {
-            let rx = Self::start_button_watch(runtime);
-            *ref_mut = Some(rx.clone());
-            rx
+    /// The frame buffer with the colour pipeline applied, as it's sent.
+    fn corrected(&self) -> [u8; BUF_SIZE * 2] {
+        let mut corrected = [0; BUF_SIZE * 2];
+        for [ir, ig, ib] in LUT {
+            let rgb = RGB8::new(self.data_buf[ir], self.data_buf[ig], self.data_buf[ib]);
+            let rgb = self.pipeline.apply(&rgb);
+            corrected[ir] = rgb.r;
+            corrected[ig] = rgb.g;
+            corrected[ib] = rgb.b;
         }
+        corrected
     }
 
     fn buf_offset(buffer_idx: usize) -> Range<usize> {
         buffer_idx * BUF_SIZE..(buffer_idx + 1) * BUF_SIZE
     }
 
-    fn write(&mut self, data: Option<&[u8]>) {
+    fn write(&mut self, data: Option<&[u8]>) -> Result<(), Error> {
         self.write_prefix(&CMD_WRITE_DISPLAY, data)
     }
-    fn write_prefix(&mut self, prefix: &[u8], data: Option<&[u8]>) {
+    fn write_prefix(&mut self, prefix: &[u8], data: Option<&[u8]>) -> Result<(), Error> {
         fn concat(a: &[u8], b: &[u8]) -> Vec<u8> {
             let mut d = a.to_owned();
             d.extend(b);
@@ -293,12 +320,13 @@ impl UnicornMini {
             let spi = &mut self.spi[i];
             if !data.is_empty() {
                 let chunk = &data[Self::buf_offset(i)];
-                spi.write_all(&concat(prefix, chunk))
-                    .expect("SPI write error");
+                transport::send(spi, &concat(prefix, chunk))?;
             } else {
-                spi.write_all(prefix).expect("SPI write error");
+                transport::send(spi, prefix)?;
             }
         }
+
+        Ok(())
     }
 }
 
@@ -308,45 +336,394 @@ impl Default for UnicornMini {
     }
 }
 
-impl Drop for UnicornMini {
+impl<T: Transport> Drop for UnicornMini<T> {
     fn drop(&mut self) {
-        self.reset();
+        self.reset().ok();
     }
 }
 
-impl Display for UnicornMini {
+impl<T: Transport> Display for UnicornMini<T> {
     fn set_xy(&mut self, x: usize, y: usize, rgb: &RGB8) {
-        let idx = x * 7 + y;
-        assert!(x < 17, "LED x index out of range: {}", idx);
-        assert!(y < 7, "LED y index out of range: {}", idx);
+        assert!(x < self.dims.width, "LED x index out of range: {}", x);
+        assert!(y < self.dims.height, "LED y index out of range: {}", y);
 
-        self.set_idx(idx, rgb);
+        if let Some(idx) = self.map.index(x, y) {
+            self.set_idx(idx, rgb);
+        }
     }
 
     fn set_idx(&mut self, idx: usize, rgb: &RGB8) {
-        assert!(idx < 119, "LED index out of range: {}", idx);
+        assert!(idx < NUM_LEDS, "LED index out of range: {}", idx);
         let [ir, ig, ib] = LUT[idx];
         self.data_buf[ir] = rgb.r;
         self.data_buf[ig] = rgb.g;
         self.data_buf[ib] = rgb.b;
     }
 
-    fn flush(&mut self) {
-        self.write(None);
+    fn flush(&mut self) -> Result<(), Error> {
+        if self.brightness_changed {
+            self.write_prefix(&[CMD_GLOBAL_BRIGHTNESS, self.brightness], Some(&[]))?;
+            self.brightness_changed = false;
+        }
+
+        let corrected = self.corrected();
+        self.write(Some(&corrected))
     }
 
-    fn reset(&mut self) {
-        self.write_prefix(&CMD_SOFT_RESET, Some(&[]));
-        self.write_prefix(&CMD_GLOBAL_BRIGHTNESS, Some(&[]));
-        self.write_prefix(&CMD_SCROLL_CTRL, Some(&[]));
-        self.write_prefix(&CMD_SYSTEM_CTRL_OFF, Some(&[]));
-        self.write_prefix(&CMD_WRITE_DISPLAY, None); //TODO without clone
-        self.write_prefix(&CMD_COM_PIN_CTRL, Some(&[]));
-        self.write_prefix(&CMD_ROW_PIN_CTRL, Some(&[]));
-        self.write_prefix(&CMD_SYSTEM_CTRL_ON, Some(&[]));
+    fn reset(&mut self) -> Result<(), Error> {
+        self.write_prefix(&CMD_SOFT_RESET, Some(&[]))?;
+        self.write_prefix(&[CMD_GLOBAL_BRIGHTNESS, self.brightness], Some(&[]))?;
+        self.brightness_changed = false;
+        self.write_prefix(&CMD_SCROLL_CTRL, Some(&[]))?;
+        self.write_prefix(&CMD_SYSTEM_CTRL_OFF, Some(&[]))?;
+        let corrected = self.corrected();
+        self.write(Some(&corrected))?;
+        self.write_prefix(&CMD_COM_PIN_CTRL, Some(&[]))?;
+        self.write_prefix(&CMD_ROW_PIN_CTRL, Some(&[]))?;
+        self.write_prefix(&CMD_SYSTEM_CTRL_ON, Some(&[]))
     }
 
     fn dimensions(&self) -> &Dimensions {
         &self.dims
     }
+
+    fn num_leds(&self) -> usize {
+        NUM_LEDS
+    }
+
+    fn set_brightness(&mut self, brightness: f32) {
+        // Uses the HT16D35's global brightness rather than scaling the colours
+        let brightness = (brightness.clamp(0.0, 1.0) * MAX_BRIGHTNESS as f32).round() as u8;
+        if brightness != self.brightness {
+            self.brightness = brightness;
+            self.brightness_changed = true;
+        }
+    }
+
+    fn brightness(&self) -> f32 {
+        self.brightness as f32 / MAX_BRIGHTNESS as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+    use crate::pimoroni::{
+        pixelmap::{Order, Serpentine},
+        transport::RecordingTransport,
+    };
+
+    fn reset_sequence() -> Vec<Vec<u8>> {
+        let mut write_display = CMD_WRITE_DISPLAY.to_vec();
+        write_display.extend([0; BUF_SIZE]);
+
+        vec![
+            CMD_SOFT_RESET.to_vec(),
+            vec![CMD_GLOBAL_BRIGHTNESS, DEFAULT_BRIGHTNESS],
+            CMD_SCROLL_CTRL.to_vec(),
+            CMD_SYSTEM_CTRL_OFF.to_vec(),
+            write_display,
+            CMD_COM_PIN_CTRL.to_vec(),
+            CMD_ROW_PIN_CTRL.to_vec(),
+            CMD_SYSTEM_CTRL_ON.to_vec(),
+        ]
+    }
+
+    fn new_mini() -> UnicornMini<RecordingTransport> {
+        UnicornMini::with_transports(
+            [RecordingTransport::new(), RecordingTransport::new()],
+            ColourPipeline::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_reset_command_sequence() {
+        let um = new_mini();
+
+        let [first, second] = um.transports();
+        assert_eq!(first.writes(), reset_sequence().as_slice());
+        assert_eq!(second.writes(), reset_sequence().as_slice());
+        assert_eq!(first.writes()[1], [0x37, 0x01]);
+    }
+
+    #[test]
+    fn test_flush_splits_buffer_between_chips() {
+        let mut um = new_mini();
+        um.transports_mut().iter_mut().for_each(|t| t.clear());
+
+        // First LED lives on the first chip, last LED on the second
+        um.set_idx(0, &RGB8::new(1, 2, 3));
+        um.set_xy(16, 6, &RGB8::new(4, 5, 6));
+        um.flush().unwrap();
+
+        let [first, second] = um.transports();
+        assert_eq!(first.writes().len(), 1);
+        assert_eq!(second.writes().len(), 1);
+
+        let first = &first.writes()[0];
+        assert_eq!(first.len(), 2 + BUF_SIZE);
+        assert_eq!(first[..2], CMD_WRITE_DISPLAY);
+        assert_eq!(first[2 + 139], 1);
+        assert_eq!(first[2 + 138], 2);
+        assert_eq!(first[2 + 137], 3);
+        assert_eq!(first.iter().skip(2).filter(|b| **b != 0).count(), 3);
+
+        let second = &second.writes()[0];
+        assert_eq!(second[..2], CMD_WRITE_DISPLAY);
+        assert_eq!(second[2 + 296 - BUF_SIZE], 4);
+        assert_eq!(second[2 + 298 - BUF_SIZE], 5);
+        assert_eq!(second[2 + 297 - BUF_SIZE], 6);
+        assert_eq!(second.iter().skip(2).filter(|b| **b != 0).count(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "LED index out of range")]
+    fn test_set_idx_out_of_range() {
+        let mut um = new_mini();
+        um.set_idx(NUM_LEDS, &RGB8::new(1, 1, 1));
+    }
+
+    #[test]
+    fn test_pixel_map() {
+        let mut um = new_mini();
+        let mut expected = new_mini();
+        um.set_pixel_map(Serpentine {
+            dims: Dimensions {
+                width: 7,
+                height: 17,
+            },
+            order: Order::RowMajor,
+        })
+        .unwrap();
+        assert_eq!(um.dimensions().width, 7);
+
+        // The second row runs backwards, from LED 13 to 7
+        um.set_xy(0, 1, &RGB8::new(1, 2, 3));
+        expected.set_idx(13, &RGB8::new(1, 2, 3));
+        assert_eq!(um.data_buf, expected.data_buf);
+
+        assert!(matches!(
+            um.set_pixel_map(Grid::UNICORN),
+            Err(Error::InvalidPixelMap(_))
+        ));
+        assert_eq!(um.dimensions().width, 7);
+    }
+
+    #[test]
+    fn test_brightness_sent_before_next_frame() {
+        let mut um = new_mini();
+        um.transports_mut().iter_mut().for_each(|t| t.clear());
+
+        um.set_brightness(0.5);
+        um.flush().unwrap();
+        um.flush().unwrap();
+
+        for t in um.transports() {
+            let writes = t.writes();
+            assert_eq!(writes.len(), 3);
+            assert_eq!(writes[0], [CMD_GLOBAL_BRIGHTNESS, 32]);
+            assert_eq!(writes[1][..2], CMD_WRITE_DISPLAY);
+            assert_eq!(writes[2][..2], CMD_WRITE_DISPLAY);
+        }
+    }
+
+    #[test]
+    fn test_brightness_is_clamped() {
+        let mut um = new_mini();
+        um.set_brightness(7.0);
+        assert_eq!(um.brightness, MAX_BRIGHTNESS);
+        um.set_brightness(-1.0);
+        assert_eq!(um.brightness, 0);
+    }
+
+    #[test]
+    fn test_brightness_can_be_restored() {
+        let mut um = new_mini();
+        let initial = um.brightness();
+        assert_eq!(initial, 1.0 / 63.0);
+
+        um.set_brightness(1.0);
+        assert_eq!(um.brightness(), 1.0);
+        um.set_brightness(initial);
+        assert_eq!(um.brightness, DEFAULT_BRIGHTNESS);
+    }
+
+    #[test]
+    fn test_pipeline_applied_on_flush() {
+        let pipeline = ColourPipeline::new(1.0, [1.0, 0.5, 1.0], 100);
+        let mut um = UnicornMini::with_transports(
+            [RecordingTransport::new(), RecordingTransport::new()],
+            pipeline,
+        )
+        .unwrap();
+        um.transports_mut().iter_mut().for_each(|t| t.clear());
+
+        um.set_idx(0, &RGB8::new(200, 100, 50));
+        um.flush().unwrap();
+
+        let frame = &um.transports()[0].writes()[0];
+        assert_eq!(frame[2 + 139], 100);
+        assert_eq!(frame[2 + 138], 50);
+        assert_eq!(frame[2 + 137], 50);
+        // The caller's colours are left alone
+        assert_eq!(um.data_buf[139], 200);
+    }
+
+    #[test]
+    fn test_pipeline_applied_on_reset() {
+        let pipeline = ColourPipeline::new(1.0, [1.0, 0.5, 1.0], 100);
+        let mut um = UnicornMini::with_transports(
+            [RecordingTransport::new(), RecordingTransport::new()],
+            pipeline,
+        )
+        .unwrap();
+        um.set_idx(0, &RGB8::new(200, 100, 50));
+        um.transports_mut().iter_mut().for_each(|t| t.clear());
+
+        um.reset().unwrap();
+
+        let frame = &um.transports()[0].writes()[4];
+        assert_eq!(frame[..2], CMD_WRITE_DISPLAY);
+        assert_eq!(frame[2 + 139], 100);
+        assert_eq!(frame[2 + 138], 50);
+    }
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[tokio::test]
+    async fn test_button_events_from_scripted_input() {
+        let mut script = ScriptedButtons::new();
+        script.push(ms(0), Button::A, true);
+        // Contact bounce
+        script.push(ms(5), Button::A, false);
+        script.push(ms(8), Button::A, true);
+        script.push(ms(8), Button::B, true);
+        script.push(ms(100), Button::A, false);
+        script.push(ms(2000), Button::B, false);
+        let t0 = script.start();
+
+        let mut um = new_mini();
+        um.set_button_input(script);
+        let mut buttons = um.buttons().unwrap();
+
+        let mut events = vec![];
+        while let Some(e) = buttons.next().await {
+            events.push((e.button, e.kind, e.timestamp - t0));
+        }
+        assert_eq!(
+            events,
+            [
+                (Button::A, EventKind::Pressed, ms(0)),
+                (Button::B, EventKind::Pressed, ms(8)),
+                (Button::A, EventKind::Released, ms(100)),
+                (Button::B, EventKind::LongPress, ms(808)),
+                (Button::B, EventKind::Released, ms(2000)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_encoder_on_same_stream() {
+        let encoder = Encoder::new(22, 23);
+        let mut script = ScriptedButtons::new();
+        script.push(ms(0), Button::Gpio(4), true);
+        for (t, pin, low) in [
+            (10, 23, true),
+            (11, 22, true),
+            (12, 23, false),
+            (13, 22, false),
+        ] {
+            script.push(ms(t), Button::Gpio(pin), low);
+        }
+        script.push(ms(100), Button::Gpio(4), false);
+
+        let mut um = new_mini();
+        um.set_input_map(InputMap {
+            buttons: vec![Button::Gpio(4)],
+            encoders: vec![encoder],
+        });
+        um.set_button_input(script);
+        let mut buttons = um.buttons().unwrap();
+
+        let mut events = vec![];
+        while let Some(e) = buttons.next_input().await {
+            events.push(match e {
+                InputEvent::Button(e) => format!("{:?} {:?}", e.button, e.kind),
+                InputEvent::Encoder(e) => format!("{} {}", e.encoder.a, e.steps),
+            });
+        }
+        assert_eq!(events, ["Gpio(4) Pressed", "22 -1", "Gpio(4) Released"]);
+    }
+
+    #[tokio::test]
+    async fn test_handles_share_one_watch() {
+        let mut script = ScriptedButtons::new();
+        script.push(ms(0), Button::X, true);
+        script.push(ms(100), Button::X, false);
+        script.push(ms(200), Button::Y, true);
+
+        let mut um = new_mini();
+        um.set_button_input(script);
+        let first = um.buttons().unwrap();
+        let mut pressed = first.last_pressed();
+        pressed.wait_for(|b| *b == Some(Button::Y)).await.unwrap();
+
+        // The scripted input was used up by the first handle, so this would
+        // fail trying the GPIO pins if it started another watch
+        let second = um.buttons().unwrap();
+        assert_eq!(*second.last_pressed().borrow(), Some(Button::Y));
+    }
+
+    /// Never sees an edge, like GPIO pins nobody touches.
+    struct Untouched(Arc<AtomicBool>);
+
+    impl ButtonInput for Untouched {
+        fn next_edge(&mut self, _deadline: Option<Instant>) -> Result<Option<Edge>, Error> {
+            std::thread::sleep(ms(1));
+            Ok(None)
+        }
+    }
+
+    impl Drop for Untouched {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_watch_stops_when_handles_dropped() {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let mut um = new_mini();
+        um.set_button_input(Untouched(stopped.clone()));
+
+        let buttons = um.buttons().unwrap();
+        let other = buttons.clone();
+        drop(buttons);
+        std::thread::sleep(ms(20));
+        assert!(!stopped.load(Ordering::Relaxed));
+
+        drop(other);
+        let start = Instant::now();
+        while !stopped.load(Ordering::Relaxed) {
+            assert!(start.elapsed() < Duration::from_secs(1));
+            std::thread::sleep(ms(1));
+        }
+    }
+
+    #[test]
+    fn test_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<UnicornMini>();
+    }
 }
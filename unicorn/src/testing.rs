@@ -0,0 +1,87 @@
+// Test doubles shared between modules' tests.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use rgb::RGB8;
+
+use crate::{
+    error::Error,
+    pimoroni::{Dimensions, Display},
+};
+
+#[derive(Default)]
+pub struct DisplayState {
+    /// Indexed as by `set_idx`, row-major for `set_xy`.
+    pub pixels: Vec<RGB8>,
+    pub flushes: usize,
+}
+
+/// Display which can be handed to code under test while the test keeps a
+/// handle on what was drawn.
+#[derive(Clone)]
+pub struct SharedDisplay {
+    dims: Dimensions,
+    pub state: Arc<Mutex<DisplayState>>,
+}
+
+impl SharedDisplay {
+    pub fn new(dims: Dimensions) -> Self {
+        SharedDisplay {
+            dims,
+            state: Arc::new(Mutex::new(DisplayState {
+                pixels: vec![RGB8::default(); dims.num_px()],
+                flushes: 0,
+            })),
+        }
+    }
+
+    pub fn pixels(&self) -> Vec<RGB8> {
+        self.state.lock().unwrap().pixels.clone()
+    }
+
+    pub fn flushes(&self) -> usize {
+        self.state.lock().unwrap().flushes
+    }
+
+    /// Poll until at least `n` flushes have happened, panicking after a second.
+    pub async fn wait_for_flushes(&self, n: usize) {
+        let start = Instant::now();
+        while self.flushes() < n {
+            assert!(
+                start.elapsed() < Duration::from_secs(1),
+                "Timed out waiting for {} flushes, saw {}",
+                n,
+                self.flushes()
+            );
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    }
+}
+
+impl Display for SharedDisplay {
+    fn set_xy(&mut self, x: usize, y: usize, rgb: &RGB8) {
+        assert!(x < self.dims.width && y < self.dims.height);
+        self.set_idx(x + y * self.dims.width, rgb);
+    }
+
+    fn set_idx(&mut self, idx: usize, rgb: &RGB8) {
+        self.state.lock().unwrap().pixels[idx] = *rgb;
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.state.lock().unwrap().flushes += 1;
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        self.state.lock().unwrap().pixels.fill(RGB8::default());
+        self.flush()
+    }
+
+    fn dimensions(&self) -> &Dimensions {
+        &self.dims
+    }
+}
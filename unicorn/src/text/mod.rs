@@ -0,0 +1,224 @@
+use std::time::Duration;
+
+use rgb::RGB8;
+
+use crate::{error::Error, pimoroni::Display};
+
+pub mod font;
+
+use font::{Font, FONT_5X7};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// Text enters on the right and moves left, like a ticker.
+    Left,
+    Right,
+}
+
+pub struct ScrollSettings {
+    pub font: &'static Font,
+    pub colour: RGB8,
+    pub background: RGB8,
+    /// Time taken to move the text by one column.
+    pub step: Duration,
+    pub direction: Direction,
+    /// Start again once the text has scrolled off the display.
+    pub looping: bool,
+}
+impl Default for ScrollSettings {
+    fn default() -> Self {
+        ScrollSettings {
+            font: &FONT_5X7,
+            colour: RGB8::new(255, 255, 255),
+            background: RGB8::new(0, 0, 0),
+            step: Duration::from_millis(100),
+            direction: Direction::Left,
+            looping: true,
+        }
+    }
+}
+
+/// Scrolls a line of text across a [`Display`], vertically centred.
+pub struct TextScroller {
+    columns: Vec<u8>,
+    settings: ScrollSettings,
+    position: usize,
+}
+
+impl TextScroller {
+    pub fn new(text: &str, settings: ScrollSettings) -> Self {
+        TextScroller {
+            columns: settings.font.render(text),
+            settings,
+            position: 0,
+        }
+    }
+
+    /// Replace the text, starting again from the edge of the display.
+    pub fn set_text(&mut self, text: &str) {
+        self.columns = self.settings.font.render(text);
+        self.position = 0;
+    }
+
+    /// Draw the current frame without flushing it.
+    pub fn draw<T: Display + ?Sized>(&self, display: &mut T) {
+        let dims = *display.dimensions();
+        let font = self.settings.font;
+        let text_width = self.columns.len() as isize;
+        let position = self.position as isize;
+
+        // Column of the text to show at the left edge of the display
+        let start = match self.settings.direction {
+            Direction::Left => position - dims.width as isize,
+            Direction::Right => text_width - position,
+        };
+        let top = (dims.height as isize - font.height as isize) / 2;
+
+        for x in 0..dims.width {
+            let column = usize::try_from(start + x as isize)
+                .ok()
+                .and_then(|i| self.columns.get(i))
+                .copied()
+                .unwrap_or(0);
+
+            for y in 0..dims.height {
+                let row = y as isize - top;
+                let lit = (0..font.height as isize).contains(&row) && column & (1 << row) != 0;
+                let rgb = if lit {
+                    self.settings.colour
+                } else {
+                    self.settings.background
+                };
+                display.set_xy(x, y, &rgb);
+            }
+        }
+    }
+
+    /// Draw and flush the current frame, then move the text on by a column.
+    ///
+    /// Returns `false` once non-looping text has scrolled off the display.
+    pub fn step<T: Display + ?Sized>(&mut self, display: &mut T) -> Result<bool, Error> {
+        let end = display.dimensions().width + self.columns.len();
+        if self.position > end {
+            if !self.settings.looping {
+                return Ok(false);
+            }
+            self.position = 0;
+        }
+
+        self.draw(display);
+        display.flush()?;
+        self.position += 1;
+
+        Ok(true)
+    }
+
+    /// Scroll on a tokio interval until non-looping text has gone, or forever.
+    pub async fn run<T: Display + ?Sized>(&mut self, display: &mut T) -> Result<(), Error> {
+        let mut interval = tokio::time::interval(self.settings.step);
+        loop {
+            interval.tick().await;
+            if !self.step(display)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{font::FONT_3X5, *};
+    use crate::{
+        emulator::snapshot::{ImageFormat, SnapshotDisplay},
+        pimoroni::Dimensions,
+    };
+
+    const ON: RGB8 = RGB8::new(9, 9, 9);
+
+    fn settings(direction: Direction, looping: bool) -> ScrollSettings {
+        ScrollSettings {
+            font: &FONT_3X5,
+            colour: ON,
+            step: Duration::from_millis(1),
+            direction,
+            looping,
+            ..Default::default()
+        }
+    }
+
+    fn display() -> SnapshotDisplay {
+        SnapshotDisplay::new(
+            Dimensions {
+                width: 4,
+                height: 7,
+            },
+            1,
+            ImageFormat::Ppm,
+        )
+    }
+
+    fn lit_columns(display: &SnapshotDisplay) -> Vec<bool> {
+        (0..4)
+            .map(|x| (0..7).any(|y| display.pixel(x, y) == ON))
+            .collect()
+    }
+
+    #[test]
+    fn test_scrolls_left_from_right_edge() {
+        let mut display = display();
+        let mut scroller = TextScroller::new("-", settings(Direction::Left, false));
+
+        scroller.step(&mut display).unwrap();
+        assert_eq!(lit_columns(&display), [false; 4]);
+        scroller.step(&mut display).unwrap();
+        assert_eq!(lit_columns(&display), [false, false, false, true]);
+        // The 3x5 minus sits in the middle row of the 7 high display
+        assert_eq!(display.pixel(3, 3), ON);
+
+        for _ in 0..4 {
+            scroller.step(&mut display).unwrap();
+        }
+        assert_eq!(lit_columns(&display), [true, true, false, false]);
+    }
+
+    #[test]
+    fn test_scrolls_right_from_left_edge() {
+        let mut display = display();
+        let mut scroller = TextScroller::new("-", settings(Direction::Right, false));
+
+        scroller.step(&mut display).unwrap();
+        assert_eq!(lit_columns(&display), [false; 4]);
+        scroller.step(&mut display).unwrap();
+        assert_eq!(lit_columns(&display), [true, false, false, false]);
+    }
+
+    #[test]
+    fn test_stops_when_not_looping() {
+        let mut display = display();
+        let mut scroller = TextScroller::new("-", settings(Direction::Left, false));
+
+        let steps = std::iter::from_fn(|| scroller.step(&mut display).unwrap().then_some(()));
+        assert_eq!(steps.count(), 4 + 3 + 1);
+        assert_eq!(lit_columns(&display), [false; 4]);
+    }
+
+    #[test]
+    fn test_loops() {
+        let mut display = display();
+        let mut scroller = TextScroller::new("-", settings(Direction::Left, true));
+
+        for _ in 0..(4 + 3 + 1) * 2 {
+            assert!(scroller.step(&mut display).unwrap());
+        }
+        assert_eq!(display.frames_flushed(), 16);
+    }
+
+    #[tokio::test]
+    async fn test_run_until_scrolled_off() {
+        let mut display = display();
+        let mut scroller = TextScroller::new("ab", settings(Direction::Left, false));
+
+        scroller.run(&mut display).await.unwrap();
+        assert_eq!(display.frames_flushed(), 4 + 7 + 1);
+    }
+}
@@ -0,0 +1,289 @@
+// End-to-end tests of keyboard capture, typing on virtual keyboards made
+// with /dev/uinput. They're skipped where uinput isn't available, which
+// usually means they need to be run as root on a real machine.
+//
+// Captures are limited to the virtual keyboards by name, so the tests don't
+// take the real keyboards, except for the one test of `grab_all_keyboards`
+// which holds them all for a moment.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    mem,
+    os::unix::io::AsRawFd,
+    process,
+    time::Duration,
+};
+
+use nix::{ioctl_none, ioctl_write_int, ioctl_write_ptr};
+use tokio::time::timeout;
+use unicorn::keyboard::{
+    capture_keyboards,
+    devices::{read_devices, PROC_DEVICES},
+    grab_all_keyboards, CaptureEvent, CaptureOptions, DeviceMatch, InputDeviceInfo, Key, KeyEvent,
+    KeyState, Keyboards, Keymap,
+};
+
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_REP: u16 = 0x14;
+const BUS_VIRTUAL: u16 = 0x06;
+
+#[repr(C)]
+struct UinputSetup {
+    bustype: u16,
+    vendor: u16,
+    product: u16,
+    version: u16,
+    name: [u8; 80],
+    ff_effects_max: u32,
+}
+
+ioctl_none!(ui_dev_create, b'U', 1);
+ioctl_none!(ui_dev_destroy, b'U', 2);
+ioctl_write_ptr!(ui_dev_setup, b'U', 3, UinputSetup);
+ioctl_write_int!(ui_set_evbit, b'U', 100);
+ioctl_write_int!(ui_set_keybit, b'U', 101);
+ioctl_write_int!(eviocgrab, b'E', 0x90);
+
+/// A keyboard which exists until it's dropped.
+struct VirtualKeyboard {
+    uinput: File,
+    name: String,
+}
+
+impl VirtualKeyboard {
+    /// Make a keyboard, or `None` if uinput isn't available.
+    fn create(test: &str) -> Option<Self> {
+        let uinput = match OpenOptions::new().write(true).open("/dev/uinput") {
+            Ok(uinput) => uinput,
+            Err(e) => {
+                eprintln!("Skipping {}: can't open /dev/uinput: {}", test, e);
+                return None;
+            }
+        };
+        let name = keyboard_name(test);
+
+        let mut setup = UinputSetup {
+            bustype: BUS_VIRTUAL,
+            vendor: 0x1234,
+            product: 0x5678,
+            version: 1,
+            name: [0; 80],
+            ff_effects_max: 0,
+        };
+        setup.name[..name.len()].copy_from_slice(name.as_bytes());
+
+        let fd = uinput.as_raw_fd();
+        unsafe {
+            ui_set_evbit(fd, EV_KEY as _).unwrap();
+            ui_set_evbit(fd, EV_REP as _).unwrap();
+            // Esc to the function keys, including all the letters
+            for code in 1..=88 {
+                ui_set_keybit(fd, code).unwrap();
+            }
+            ui_dev_setup(fd, &setup).unwrap();
+            ui_dev_create(fd).unwrap();
+        }
+
+        Some(VirtualKeyboard { uinput, name })
+    }
+
+    fn emit(&mut self, type_: u16, code: u16, value: i32) -> io::Result<()> {
+        let event = libc::input_event {
+            time: libc::timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+            type_,
+            code,
+            value,
+        };
+        let bytes: [u8; mem::size_of::<libc::input_event>()] = unsafe { mem::transmute(event) };
+        self.uinput.write_all(&bytes)
+    }
+
+    fn tap(&mut self, key: Key) -> io::Result<()> {
+        self.emit(EV_KEY, key.code(), 1)?;
+        self.emit(EV_SYN, 0, 0)?;
+        self.emit(EV_KEY, key.code(), 0)?;
+        self.emit(EV_SYN, 0, 0)
+    }
+}
+
+impl Drop for VirtualKeyboard {
+    fn drop(&mut self) {
+        unsafe {
+            ui_dev_destroy(self.uinput.as_raw_fd()).ok();
+        }
+    }
+}
+
+/// Tests run in parallel, and other processes may be running them too.
+fn keyboard_name(test: &str) -> String {
+    format!("unicorn {} {}", test, process::id())
+}
+
+/// Capture just the keyboard called `name`.
+fn options(name: &str) -> CaptureOptions {
+    CaptureOptions {
+        include: vec![DeviceMatch::Name(name.to_owned())],
+        keymap: Some(Keymap::Us),
+        ..Default::default()
+    }
+}
+
+async fn next(keyboards: &mut Keyboards) -> CaptureEvent {
+    timeout(Duration::from_secs(5), keyboards.next())
+        .await
+        .expect("Timed out waiting for a capture event")
+        .expect("Capture stopped")
+}
+
+async fn next_added(keyboards: &mut Keyboards) -> InputDeviceInfo {
+    match next(keyboards).await {
+        CaptureEvent::DeviceAdded(info) => info,
+        event => panic!("Expected a device to be added, got {:?}", event),
+    }
+}
+
+async fn next_key(keyboards: &mut Keyboards) -> KeyEvent {
+    match next(keyboards).await {
+        CaptureEvent::Key(event) => event,
+        event => panic!("Expected a key event, got {:?}", event),
+    }
+}
+
+/// Whether anyone has grabbed the device, found by trying to grab it too.
+fn is_grabbed(info: &InputDeviceInfo) -> bool {
+    let path = format!("/dev/input/{}", info.event_node().unwrap());
+    let device = File::open(path).unwrap();
+    match unsafe { eviocgrab(device.as_raw_fd(), 1) } {
+        Ok(_) => {
+            unsafe { eviocgrab(device.as_raw_fd(), 0).unwrap() };
+            false
+        }
+        Err(nix::Error::Sys(nix::errno::Errno::EBUSY)) => true,
+        Err(e) => panic!("Failed to test grab: {}", e),
+    }
+}
+
+#[tokio::test]
+async fn test_captures_existing_keyboard() {
+    let Some(mut keyboard) = VirtualKeyboard::create("existing") else {
+        return;
+    };
+
+    let mut keyboards = capture_keyboards(options(&keyboard.name)).unwrap();
+    let info = next_added(&mut keyboards).await;
+    assert_eq!(info.name, keyboard.name);
+    assert_eq!((info.vendor, info.product), (0x1234, 0x5678));
+    assert!(info.is_keyboard());
+
+    keyboard.tap(Key::A).unwrap();
+    let pressed = next_key(&mut keyboards).await;
+    assert_eq!(pressed.key, Key::A);
+    assert_eq!(pressed.state, KeyState::Pressed);
+    assert_eq!(pressed.text, Some('a'));
+    assert_eq!(pressed.device, keyboard.name);
+    let released = next_key(&mut keyboards).await;
+    assert_eq!((released.key, released.state), (Key::A, KeyState::Released));
+
+    keyboards.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_hotplug() {
+    if let Err(e) = OpenOptions::new().write(true).open("/dev/uinput") {
+        return eprintln!("Skipping hotplug: can't open /dev/uinput: {}", e);
+    }
+
+    let mut keyboards = capture_keyboards(options(&keyboard_name("hotplug"))).unwrap();
+    // Give the watch a moment to start, so the keyboard is seen being added
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut keyboard = VirtualKeyboard::create("hotplug").unwrap();
+    let added = next_added(&mut keyboards).await;
+    assert_eq!(added.name, keyboard.name);
+
+    keyboard.tap(Key::Digit1).unwrap();
+    assert_eq!(next_key(&mut keyboards).await.text, Some('1'));
+    assert_eq!(next_key(&mut keyboards).await.state, KeyState::Released);
+
+    drop(keyboard);
+    match next(&mut keyboards).await {
+        CaptureEvent::DeviceRemoved(info) => assert_eq!(info, added),
+        event => panic!("Expected the device to be removed, got {:?}", event),
+    }
+
+    keyboards.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_grab_released() {
+    let Some(keyboard) = VirtualKeyboard::create("grab") else {
+        return;
+    };
+
+    let mut keyboards = capture_keyboards(options(&keyboard.name)).unwrap();
+    let info = next_added(&mut keyboards).await;
+    assert!(is_grabbed(&info));
+    keyboards.shutdown().await;
+    assert!(!is_grabbed(&info));
+
+    let mut keyboards = capture_keyboards(CaptureOptions {
+        exclusive: false,
+        ..options(&keyboard.name)
+    })
+    .unwrap();
+    let info = next_added(&mut keyboards).await;
+    assert!(!is_grabbed(&info));
+
+    // Dropping the handle releases the devices too, if not straight away
+    let mut keyboards = capture_keyboards(options(&keyboard.name)).unwrap();
+    let info = next_added(&mut keyboards).await;
+    assert!(is_grabbed(&info));
+    drop(keyboards);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(!is_grabbed(&info));
+}
+
+#[tokio::test]
+async fn test_grab_all_keyboards() {
+    let Some(mut keyboard) = VirtualKeyboard::create("grab-all") else {
+        return;
+    };
+
+    let mut keys = grab_all_keyboards(Some(Keymap::Us));
+    // Nothing says when the keyboard has been found, so keep typing until
+    // it has, skipping anything typed on the real keyboards
+    let pressed = timeout(Duration::from_secs(5), async {
+        loop {
+            keyboard.tap(Key::B).unwrap();
+            let typed = timeout(Duration::from_millis(100), async {
+                loop {
+                    let event = keys.recv().await.expect("Capture stopped");
+                    if event.device == keyboard.name && event.state == KeyState::Pressed {
+                        return event;
+                    }
+                }
+            });
+            if let Ok(event) = typed.await {
+                return event;
+            }
+        }
+    })
+    .await
+    .expect("Timed out waiting for the keyboard to be grabbed");
+    assert_eq!((pressed.key, pressed.text), (Key::B, Some('b')));
+
+    let info = read_devices(PROC_DEVICES)
+        .unwrap()
+        .into_iter()
+        .find(|d| d.name == keyboard.name)
+        .unwrap();
+    assert!(is_grabbed(&info));
+    drop(keys);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(!is_grabbed(&info));
+}